@@ -1,127 +1,279 @@
 use crate::*;
-use rand::{Rng, SeedableRng};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
-use std::{collections::BTreeMap, fs::OpenOptions, io::Write, ops::Range};
-
-// #[cfg(feature = "parallel")]
-// use rayon::prelude::*;
-
-pub struct MarkovJunior<'a> {
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+    ops::Range,
+};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Rows per chunk when scanning the grid in parallel. Smaller scans, like the ones after every
+/// rule step, stay on the current thread.
+#[cfg(feature = "parallel")]
+const PARALLEL_ROWS: usize = 16;
+
+/// State of a run: the grid, its random number generator and caches derived from the grid.
+pub struct MarkovJunior {
+    /// Index into `alphabet` of the symbol of every cell, row by row and layer by layer. Writing
+    /// it directly leaves `canonical_forms` behind, unlike [`MarkovJunior::put`] and rules.
     pub grid: Vec<u8>,
     pub width: usize,
     pub height: usize,
-    pub canonical_forms: BTreeMap<(usize, usize), Vec<RotatedSeq>>,
+    /// Number of layers along z, 1 for flat grids.
+    pub length: usize,
+    /// Axes along which matching, writes and canonical forms continue at the opposite edge.
+    pub wrap: Wrap,
+    /// Columns, rows and layers that windows of patterns with border cells can hang off the grid,
+    /// along the axes that don't wrap around. Matches and canonical forms are kept for the windows
+    /// starting in it too, at the positions past the grid: `width + margin - 1` is the column
+    /// left of the grid. Grows with the patterns of the rules run so far. Flat grids have no
+    /// margin along z.
+    pub margin: usize,
+    /// Symbols the grid can hold, e.g. for renderers that assign each one a color. Symbols
+    /// written by patterns are appended if they aren't part of it yet.
+    pub alphabet: Alphabet,
+    /// Forms of the `long` by `short` and the `short` by `long` window at every anchor, for the
+    /// canonical keys of the running rule and of the rules running markov nodes keep matches for,
+    /// kept up to date as the grid changes. Only flat grids use them.
+    pub canonical_forms: BTreeMap<CanonicalKey, CanonicalForms>,
+    /// Number of pattern applications.
     pub changes: usize,
+    /// Number of cell writes that changed the symbol of a cell.
+    pub changed_cells: usize,
     pub rng: ChaCha8Rng,
     pub seed: u64,
-    pub maybe_output_file: Option<&'a str>,
-    pub maybe_log_cmd: Option<&'a str>,
+    /// Cells changed by the current rule step, reported to the observer once it is done.
+    step_cells: Vec<(usize, usize, usize)>,
+    /// Logs of the running markov nodes, innermost last.
+    markov_logs: Vec<MarkovLog>,
+}
+
+/// What a running markov node needs for the matches it keeps for its rules to catch up with the
+/// grid. See [`MarkovJunior::apply_markov_rule_step`].
+#[derive(Default)]
+struct MarkovLog {
+    /// Cells changed since the node started.
+    cells: Vec<(usize, usize, usize)>,
+    /// Canonical keys of the rules it keeps matches for, whose forms are kept up to date.
+    canonical_keys: BTreeSet<CanonicalKey>,
+}
+
+/// Matches of a rule inside a running markov node, kept between its activations, and the length
+/// of the node's log of changed cells they are up to date with.
+pub(crate) struct KeptMatches {
+    cache: MatchCache,
+    logged: usize,
+    /// Margin the matches are kept for, since other rules can widen it.
+    margin: usize,
 }
 
-impl MarkovJunior<'_> {
+impl MarkovJunior {
     pub fn new(default: char, width: usize, height: usize, seed: Option<u64>) -> Self {
+        Self::new_3d(default, width, height, 1, seed)
+    }
+
+    pub fn new_3d(
+        default: char,
+        width: usize,
+        height: usize,
+        length: usize,
+        seed: Option<u64>,
+    ) -> Self {
         let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
 
         MarkovJunior {
-            grid: vec![default as u8; width * height],
+            grid: vec![0; width * height * length],
             width,
             height,
+            length,
+            wrap: Wrap::NONE,
+            margin: 0,
+            alphabet: Alphabet::from_symbols([default]),
             canonical_forms: BTreeMap::new(),
             changes: 0,
+            changed_cells: 0,
             rng: ChaCha8Rng::seed_from_u64(seed),
             seed,
-            maybe_output_file: None,
-            maybe_log_cmd: None,
+            step_cells: Vec::new(),
+            markov_logs: Vec::new(),
         }
     }
 
+    /// Grid holding the symbols of `data`, row by row. Data past the first `width` by `height`
+    /// layer makes up the layers above it.
     pub fn new_grid(data: &str, width: usize, height: usize, seed: Option<u64>) -> Self {
         let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut alphabet = Alphabet::default();
+        let grid = data.chars().map(|c| alphabet.insert(c)).collect::<Vec<_>>();
 
         MarkovJunior {
-            grid: data.chars().map(|c| c as u8).collect(),
+            length: grid.len() / (width * height),
+            grid,
             width,
             height,
+            wrap: Wrap::NONE,
+            margin: 0,
+            alphabet,
             canonical_forms: BTreeMap::new(),
             changes: 0,
+            changed_cells: 0,
             rng: ChaCha8Rng::seed_from_u64(seed),
             seed,
-            maybe_output_file: None,
-            maybe_log_cmd: None,
+            step_cells: Vec::new(),
+            markov_logs: Vec::new(),
         }
     }
 
+    pub fn put(&mut self, x: usize, y: usize, z: usize, symbol: char) {
+        self.grid[(z * self.height + y) * self.width + x] = self.alphabet.insert(symbol);
+        self.update_canonical_forms(&[(x, y, z)]);
+        for log in &mut self.markov_logs {
+            log.cells.push((x, y, z));
+        }
+    }
+
+    /// Puts the second symbol of the alphabet in the centre cell, like `origin="True"`.
+    pub fn put_origin(&mut self) {
+        let symbol = self.alphabet.symbol(1);
+        self.put(self.width / 2, self.height / 2, self.length / 2, symbol);
+    }
+
+    /// Makes the grid wrap around along the axes of `wrap`.
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = wrap;
+        self.canonical_forms.clear();
+        self
+    }
+
+    /// Replaces the alphabet, keeping the symbols of the grid.
+    pub fn with_alphabet(mut self, alphabet: Alphabet) -> Self {
+        let symbols = self.grid_symbols();
+        self.alphabet = alphabet;
+        self.set_grid_symbols(&symbols);
+        self
+    }
+
+    /// Symbol of every cell, row by row and layer by layer.
+    pub fn grid_symbols(&self) -> Vec<char> {
+        self.grid
+            .iter()
+            .map(|&index| self.alphabet.symbol(index))
+            .collect()
+    }
+
+    pub fn set_grid_symbols(&mut self, symbols: &[char]) {
+        self.grid = symbols
+            .iter()
+            .map(|&symbol| self.alphabet.insert(symbol))
+            .collect();
+        self.canonical_forms.clear();
+    }
+
+    /// Runs `sequence` to completion, finishing the run if it is the root.
     pub fn apply_sequence(&mut self, sequence: &Sequence, is_root: bool) -> bool {
-        let steps = sequence.steps.unwrap_or(self.width * self.height);
-        let mut any_change = false;
+        self.apply_sequence_observed(sequence, is_root, &mut ())
+    }
 
-        for _ in 0..steps {
-            let mut step_change = false;
+    /// Same as [`MarkovJunior::apply_sequence`], telling `observer` about every node and change.
+    pub fn apply_sequence_observed(
+        &mut self,
+        sequence: &Sequence,
+        is_root: bool,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        Walker::new(self, observer, sequence, false, is_root).run(self, observer, sequence)
+    }
 
-            for rule_or_sequence in &sequence.vec {
-                let prev_changes = self.changes;
+    /// Runs the children as a Markov algorithm: every time a child changes the grid, execution
+    /// goes back to the first child, so earlier children always take priority over later ones.
+    /// Rules advance a single step per activation. Finishes once no child can change anything.
+    pub fn apply_markov(&mut self, markov: &Sequence) -> bool {
+        Walker::new(self, &mut (), markov, true, false).run(self, &mut (), markov)
+    }
 
-                match rule_or_sequence {
-                    RuleOrSequence::Rule(rule) => {
-                        step_change |= self.apply_rule(rule);
+    /// Starts the log of changed cells of a markov node, which lasts until
+    /// [`MarkovJunior::finish_markov`].
+    pub(crate) fn start_markov(&mut self) {
+        self.markov_logs.push(MarkovLog::default());
+    }
 
-                        if is_root {
-                            Self::print_rule(rule, Some(self.changes - prev_changes));
-                        }
-                    }
-                    RuleOrSequence::Sequence(nested_sequence) => {
-                        step_change |= self.apply_sequence(nested_sequence, false);
-
-                        if is_root {
-                            println!("Sequence changes: {}", self.changes - prev_changes);
-                            for rule_or_sequence in &nested_sequence.vec {
-                                match rule_or_sequence {
-                                    RuleOrSequence::Sequence(_) => {
-                                        unreachable!("Deep nested sequences are not allowed");
-                                    }
-                                    RuleOrSequence::Rule(rule) => {
-                                        Self::print_rule(rule, None);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+    pub(crate) fn finish_markov(&mut self) {
+        self.markov_logs.pop();
+    }
 
-                if is_root {
-                    self.try_log_to_output_file();
-                    self.trye_log_command();
+    /// Advances the `index`th child of the innermost running markov node, a rule, by a single
+    /// step. Its matches are kept in `kept` between activations, catching up with the cells
+    /// changed since the previous one instead of being found again on the whole grid.
+    pub(crate) fn apply_markov_rule_step(
+        &mut self,
+        rule: &Rule,
+        kept: &mut [Option<KeptMatches>],
+        index: usize,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        let markov_log = self
+            .markov_logs
+            .last_mut()
+            .expect("no markov node is running");
+        markov_log
+            .canonical_keys
+            .extend(Self::canonical_keys(rule).into_iter().cloned());
+        // lent out so that the matches can be refreshed from it
+        let mut log = std::mem::take(&mut markov_log.cells);
+
+        // matches that are a whole grid behind are as cheap to find again, which bounds the log
+        if log.len() > self.grid.len() {
+            let oldest = log.len() - self.grid.len();
+            for slot in kept.iter_mut() {
+                if slot.as_ref().is_some_and(|kept| kept.logged < oldest) {
+                    *slot = None;
                 }
             }
-
-            any_change |= step_change;
-
-            if !step_change {
-                break;
+            let start = kept
+                .iter()
+                .flatten()
+                .map(|kept| kept.logged)
+                .min()
+                .unwrap_or(log.len());
+            log.drain(..start);
+            for kept in kept.iter_mut().flatten() {
+                kept.logged -= start;
             }
         }
 
-        if !is_root {
-            self.try_log_to_output_file();
-            self.trye_log_command();
-        }
-
-        any_change
+        let mut cache = match kept[index].take() {
+            Some(KeptMatches {
+                mut cache,
+                logged,
+                margin,
+            }) if margin == self.margin => {
+                self.refresh_matches(rule, &mut cache, &log[logged..]);
+                cache
+            }
+            _ => self.start_rule(rule),
+        };
+        self.markov_logs.last_mut().unwrap().cells = log;
+
+        let applied = self.apply_rule_step(rule, &mut cache, observer);
+        kept[index] = Some(KeptMatches {
+            cache,
+            logged: self.markov_logs.last().unwrap().cells.len(),
+            margin: self.margin,
+        });
+        applied
     }
 
     pub fn apply_rule(&mut self, rule: &Rule) -> bool {
-        let steps = rule.steps.unwrap_or(self.width * self.height * 16);
-        self.precompute_canonical_forms(rule);
-        let mut cache = self.compute_cache(rule, &(0..self.width), &(0..self.height));
+        let steps = rule.steps.unwrap_or(self.grid.len() * 16);
+        let mut cache = self.start_rule(rule);
 
         let mut any_change = false;
 
         for _ in 0..steps {
-            let step_change = match rule.kind {
-                RuleKind::One => self.apply_one_rule(rule, &mut cache),
-                RuleKind::All => self.apply_all_rule(rule, &mut cache),
-                RuleKind::Parallel => self.apply_parallel_rule(rule, &mut cache),
-            };
+            let step_change = self.apply_rule_step(rule, &mut cache, &mut ());
 
             any_change |= step_change;
 
@@ -133,126 +285,217 @@ impl MarkovJunior<'_> {
         any_change
     }
 
-    pub fn pattern_fits_canonical(&self, x: usize, y: usize, pattern: &Pattern) -> Option<isize> {
-        // ensure pattern definitely fits within the grid boundaries
-        if x + pattern.width > self.width || y + pattern.height > self.height {
-            return None;
-        }
+    /// Brings the canonical forms up to date with the grid and finds every match of `rule`, which
+    /// `apply_rule_step` keeps up to date from then on.
+    pub(crate) fn start_rule(&mut self, rule: &Rule) -> MatchCache {
+        self.precompute_canonical_forms(rule);
+        self.compute_cache(rule)
+    }
 
-        let canonical_key =
-            PatternRule::calculate_canonical_key(pattern.width, pattern.height).unwrap();
-        let precalculated_forms = self
-            .canonical_forms
-            .get(&canonical_key)
-            .expect("Canonical form should be precalculated for this key");
-
-        let index = y * self.width + x;
-        let grid_canonical_form = &precalculated_forms[index];
-        let pattern_canonical_form = &pattern.canonical_form.as_ref().unwrap();
-
-        if grid_canonical_form.data == pattern_canonical_form.data {
-            Some(Pattern::calculate_relative_rotation(
-                grid_canonical_form.rotation,
-                pattern_canonical_form.rotation,
-            ))
-        } else {
-            None
+    pub(crate) fn apply_rule_step(
+        &mut self,
+        rule: &Rule,
+        cache: &mut MatchCache,
+        observer: &mut dyn Observer,
+    ) -> bool {
+        let applied = match rule.kind {
+            RuleKind::One => self.apply_one_rule(rule, cache),
+            RuleKind::All => self.apply_all_rule(rule, cache),
+            RuleKind::Parallel(conflicts) => self.apply_parallel_rule(rule, cache, conflicts),
+        };
+
+        if !self.step_cells.is_empty() {
+            let step_cells = std::mem::take(&mut self.step_cells);
+            self.update_around(rule, cache, &step_cells);
+            for log in &mut self.markov_logs {
+                log.cells.extend_from_slice(&step_cells);
+            }
+            observer.on_cells_changed(self, &step_cells);
+            self.step_cells = step_cells;
+            self.step_cells.clear();
         }
+        applied
     }
 
-    pub fn pattern_fits(&self, x: usize, y: usize, pattern: &Pattern) -> Option<isize> {
-        let grid_width = self.width;
-        let grid = &self.grid;
-
-        'rotated_seq: for rotated_seq in pattern.unique_rotations.iter() {
-            let pattern_data = &rotated_seq.data;
+    /// Same as [`MarkovJunior::pattern_fits`] for patterns with a canonical key, using the forms
+    /// precomputed for it. Canonical forms are only kept for flat grids.
+    pub fn pattern_fits_canonical(
+        &self,
+        x: usize,
+        y: usize,
+        pattern: &Pattern,
+    ) -> Option<Transform> {
+        let canonical_key = pattern.canonical_key.as_ref().unwrap();
+        let index = y * self.anchor_width() + x;
+        let fits_grid = |width: usize, height: usize| {
+            pattern.has_border || self.window_fits(x, y, 0, width, height, 1)
+        };
 
-            // ensure pattern definitely fits within the grid boundaries
-            if x + rotated_seq.width > self.width || y + rotated_seq.height > self.height {
-                continue;
+        let CanonicalKey { long, short } = *canonical_key;
+        let ids = match self
+            .canonical_forms
+            .get(canonical_key)
+            .expect("Canonical form should be precalculated for this key")
+        {
+            CanonicalForms::Packed(windows) => {
+                let windows = windows[index];
+                // symbols that share their code with others are told apart cell by cell
+                let exact = |packed: &PackedRotation| {
+                    packed.exact && self.alphabet.symbols().iter().all(|&s| has_own_code(s))
+                };
+                // the first of `cell_rotations` that fits, like `pattern_fits`
+                return pattern
+                    .packed_rotations
+                    .iter()
+                    .find(|packed| {
+                        let (width, height) = [(long, short), (short, long)][packed.orientation];
+                        fits_grid(width, height)
+                            && packed.fits(windows[packed.orientation])
+                            && (exact(packed)
+                                || self.pattern_fits_rotation(x, y, 0, pattern, packed.rotation))
+                    })
+                    .map(|packed| packed.rotation);
             }
+            CanonicalForms::Ids(ids) => ids,
+        };
+        let pattern_id = pattern.canonical_id.unwrap();
 
-            for py in 0..rotated_seq.height {
-                for px in 0..rotated_seq.width {
-                    let pattern_char = pattern_data[py * rotated_seq.width + px];
-                    if pattern_char != ANYTHING {
-                        let grid_char = grid[(y + py) * grid_width + (x + px)] as char;
-                        if pattern_char != grid_char {
-                            continue 'rotated_seq;
-                        }
-                    }
-                }
-            }
+        let rotations = [(long, short), (short, long)]
+            .into_iter()
+            .zip(ids[index])
+            .filter(|&((width, height), grid_id)| {
+                fits_grid(width, height) && grid_id.hash == pattern_id.hash
+            })
+            // both are turned into the same canonical form
+            .map(|(_, grid_id)| pattern_id.rotation.then(grid_id.rotation.inverse()))
+            // equal hashes are confirmed cell by cell, so collisions can't match
+            .filter(|&rotation| self.pattern_fits_rotation(x, y, 0, pattern, rotation))
+            .collect::<Vec<_>>();
 
-            return Some(rotated_seq.rotation);
-        }
+        pattern.first_allowed_rotation(&rotations)
+    }
 
-        None
+    pub fn pattern_fits(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+        pattern: &Pattern,
+    ) -> Option<Transform> {
+        pattern
+            .cell_rotations
+            .iter()
+            .find(|rotated_seq| {
+                self.rotated_seq_fits(x, y, z, rotated_seq, pattern, PatternCell::matches)
+            })
+            .map(|rotated_seq| rotated_seq.rotation)
     }
 
-    fn apply_one_rule(
-        &mut self,
-        rule: &Rule,
-        cache: &mut BTreeMap<(usize, usize), Vec<PatternMatch>>,
+    /// Checks that `pattern` matches at `x`, `y`, `z` in exactly the given rotation.
+    pub fn pattern_fits_rotation(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+        pattern: &Pattern,
+        rotation: Transform,
     ) -> bool {
-        let valid_patterns = Self::cached_patterns(cache);
+        if let Some(rotated_seq) = pattern
+            .cell_rotations
+            .iter()
+            .find(|rotated_seq| rotated_seq.rotation == rotation)
+        {
+            return self.rotated_seq_fits(x, y, z, rotated_seq, pattern, PatternCell::matches);
+        }
+
+        // canonical matches can be in any allowed rotation, including the duplicates left out of
+        // `cell_rotations`, which only happens for patterns without wildcards
+        self.rotated_seq_fits(
+            x,
+            y,
+            z,
+            pattern.rotated(rotation),
+            pattern,
+            |&pattern_char, grid_char| pattern_char == ANYTHING || pattern_char == grid_char,
+        )
+    }
 
-        if valid_patterns.is_empty() {
+    /// Checks the cells of `rotated_seq`, a rotation of `pattern`, at `x`, `y`, `z`. Only the
+    /// windows of patterns with border cells can hang off the grid, where `matches` sees
+    /// `NOTHING`.
+    fn rotated_seq_fits<T>(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+        rotated_seq: &RotatedSeq<T>,
+        pattern: &Pattern,
+        matches: impl Fn(&T, char) -> bool,
+    ) -> bool {
+        let RotatedSeq {
+            width,
+            height,
+            depth,
+            ..
+        } = *rotated_seq;
+        // ensure pattern definitely fits within the grid boundaries
+        if !pattern.has_border && !self.window_fits(x, y, z, width, height, depth) {
             return false;
         }
+        // most windows need neither wrapping around nor the margin
+        let inside =
+            x + width <= self.width && y + height <= self.height && z + depth <= self.length;
+
+        for pz in 0..depth {
+            for py in 0..height {
+                let pattern_row = &rotated_seq.data[(pz * height + py) * width..][..width];
+                let grid_row = ((z + pz) * self.height + y + py) * self.width + x;
+
+                for (px, pattern_cell) in pattern_row.iter().enumerate() {
+                    let grid_char = if inside {
+                        self.alphabet.symbol(self.grid[grid_row + px])
+                    } else {
+                        self.symbol_at(x, y, z, px, py, pz)
+                    };
+                    if !matches(pattern_cell, grid_char) {
+                        return false;
+                    }
+                }
+            }
+        }
 
-        let total_weight: f32 = valid_patterns
-            .iter()
-            .map(|pattern_match| pattern_match.probability.unwrap_or(DEFAULT_PROBABILITY))
-            .sum();
-        let mut choice = self.rng.gen::<f32>() * total_weight;
-        let mut selected_change = None;
+        true
+    }
 
-        for pattern_match in valid_patterns {
-            // println!("{:?}", pattern_match);
-            choice -= pattern_match.probability.unwrap_or(DEFAULT_PROBABILITY);
-
-            if choice <= 0.0 {
-                let pattern_rule = &rule.patterns[pattern_match.pattern_index];
-                let pattern = pattern_rule.output.clone();
-                let is_canonical_key = pattern_rule.canonical_key.is_some();
-
-                selected_change = Some((
-                    pattern_match.x,
-                    pattern_match.y,
-                    pattern,
-                    pattern_match.rotation,
-                    is_canonical_key,
-                ));
-                break;
-            }
+    fn apply_one_rule(&mut self, rule: &Rule, cache: &mut MatchCache) -> bool {
+        // matches that all have a zero probability can never be picked
+        if cache.total_weight() <= 0.0 {
+            return false;
         }
 
-        if let Some((x, y, pattern, rotation, is_canonical_key)) = selected_change {
-            self.apply_pattern(x, y, &pattern, rotation);
+        let choice = self.rng.gen::<f64>() * cache.total_weight();
+        let Some(pattern_match) = cache.weighted(choice) else {
+            return false;
+        };
 
-            let size = std::cmp::max(pattern.width, pattern.height);
-            let x_range = Self::x_range(x, size, self.width);
-            let y_range = Self::x_range(y, size, self.height);
-            if is_canonical_key {
-                self.update_canonical_forms(rule, &x_range, &y_range);
-            }
-            cache.extend(self.compute_cache(rule, &x_range, &y_range));
+        let pattern_rule = &rule.patterns[pattern_match.pattern_index];
+        let PatternMatch {
+            x, y, z, rotation, ..
+        } = *pattern_match;
+        let pattern = &pattern_rule.output;
 
-            return true;
-        }
+        self.apply_pattern(x, y, z, pattern, rotation);
 
-        false
+        true
     }
 
-    fn apply_all_rule(
-        &mut self,
-        rule: &Rule,
-        cache: &mut BTreeMap<(usize, usize), Vec<PatternMatch>>,
-    ) -> bool {
-        let valid_patterns = Self::cached_patterns(cache);
+    /// Applies the matches in random order, skipping the ones that no longer fit the grid or
+    /// that would write to a cell already written during this step.
+    fn apply_all_rule(&mut self, rule: &Rule, cache: &mut MatchCache) -> bool {
+        let mut valid_patterns = cache.matches().iter().collect::<Vec<_>>();
+        valid_patterns.shuffle(&mut self.rng);
+        let mut written = vec![false; self.grid.len()];
         let mut applied = false;
-        let mut changes = Vec::new();
 
         for pattern_match in valid_patterns {
             if let Some(probability) = pattern_match.probability {
@@ -263,49 +506,57 @@ impl MarkovJunior<'_> {
             }
 
             let pattern_rule = &rule.patterns[pattern_match.pattern_index];
-            let pattern = pattern_rule.output.clone();
-            let is_canonical_key = pattern_rule.canonical_key.is_some();
+            if !self.pattern_fits_rotation(
+                pattern_match.x,
+                pattern_match.y,
+                pattern_match.z,
+                &pattern_rule.input,
+                pattern_match.rotation,
+            ) {
+                continue;
+            }
 
-            self.apply_pattern(
+            let pattern = &pattern_rule.output;
+            let written_cells = self.written_cells(
                 pattern_match.x,
                 pattern_match.y,
-                &pattern,
+                pattern_match.z,
+                pattern,
                 pattern_match.rotation,
             );
+            if written_cells.iter().any(|&(index, _)| written[index]) {
+                continue;
+            }
+            for (index, _) in written_cells {
+                written[index] = true;
+            }
 
-            changes.push((
+            self.apply_pattern(
                 pattern_match.x,
                 pattern_match.y,
-                pattern.width,
-                pattern.height,
-                is_canonical_key,
-            ));
-            applied = true;
-        }
+                pattern_match.z,
+                pattern,
+                pattern_match.rotation,
+            );
 
-        for (x, y, pattern_width, pattern_height, is_canonical_key) in changes {
-            let size = std::cmp::max(pattern_width, pattern_height);
-            let x_range = Self::x_range(x, size, self.width);
-            let y_range = Self::x_range(y, size, self.height);
-            if is_canonical_key {
-                self.update_canonical_forms(rule, &x_range, &y_range);
-            }
-            cache.extend(self.compute_cache(rule, &x_range, &y_range));
+            applied = true;
         }
 
         applied
     }
 
+    /// Decides every match on the grid as it was before the step, then writes all outputs at
+    /// once. Matches conflict when they write different symbols to the same cell, which
+    /// `conflicts` resolves.
     fn apply_parallel_rule(
         &mut self,
         rule: &Rule,
-        cache: &mut BTreeMap<(usize, usize), Vec<PatternMatch>>,
+        cache: &mut MatchCache,
+        conflicts: ConflictPolicy,
     ) -> bool {
-        let valid_patterns = Self::cached_patterns(cache);
-        let mut applied = false;
-        let mut changes = Vec::new();
+        let mut selected = Vec::new();
 
-        for pattern_match in valid_patterns {
+        for pattern_match in cache.matches() {
             if let Some(probability) = pattern_match.probability {
                 let choise = self.rng.gen::<f32>();
                 if choise > probability {
@@ -313,253 +564,582 @@ impl MarkovJunior<'_> {
                 }
             }
 
-            let pattern_rule = &rule.patterns[pattern_match.pattern_index];
-            let output = pattern_rule.output.clone();
-
-            changes.push((
+            let output = &rule.patterns[pattern_match.pattern_index].output;
+            let written_cells = self.written_cells(
                 pattern_match.x,
                 pattern_match.y,
+                pattern_match.z,
                 output,
                 pattern_match.rotation,
-                pattern_rule.canonical_key.is_some(),
-            ));
-            applied = true;
+            );
+            selected.push((pattern_match, written_cells));
         }
 
-        for (x, y, pattern, rotation, is_canonical_key) in changes {
-            self.apply_pattern(x, y, &pattern, rotation);
+        // symbol every cell is written with so far, if any
+        let mut claimed: Vec<Option<char>> = vec![None; self.grid.len()];
+        let conflicts_with_claimed = |claimed: &[Option<char>], written_cells: &[(usize, char)]| {
+            written_cells.iter().any(|&(index, symbol)| {
+                claimed[index].is_some_and(|claimed_symbol| claimed_symbol != symbol)
+            })
+        };
 
-            let size = std::cmp::max(pattern.width, pattern.height);
-            let x_range = Self::x_range(x, size, self.width);
-            let y_range = Self::x_range(y, size, self.height);
-            if is_canonical_key {
-                self.update_canonical_forms(rule, &x_range, &y_range);
+        match conflicts {
+            ConflictPolicy::Random => {
+                selected.shuffle(&mut self.rng);
+                selected.retain(|(_, written_cells)| {
+                    if conflicts_with_claimed(&claimed, written_cells) {
+                        return false;
+                    }
+                    for &(index, symbol) in written_cells {
+                        claimed[index] = Some(symbol);
+                    }
+                    true
+                });
+            }
+            ConflictPolicy::Skip => {
+                let mut conflicted = vec![false; self.grid.len()];
+                for (_, written_cells) in &selected {
+                    for &(index, symbol) in written_cells {
+                        match claimed[index] {
+                            Some(claimed_symbol) if claimed_symbol != symbol => {
+                                conflicted[index] = true
+                            }
+                            _ => claimed[index] = Some(symbol),
+                        }
+                    }
+                }
+                selected.retain(|(_, written_cells)| {
+                    written_cells.iter().all(|&(index, _)| !conflicted[index])
+                });
             }
-            cache.extend(self.compute_cache(rule, &x_range, &y_range));
+        }
+
+        let applied = !selected.is_empty();
+        for (pattern_match, _) in selected {
+            let output = &rule.patterns[pattern_match.pattern_index].output;
+            self.apply_pattern(
+                pattern_match.x,
+                pattern_match.y,
+                pattern_match.z,
+                output,
+                pattern_match.rotation,
+            );
         }
 
         applied
     }
 
-    pub fn apply_pattern(&mut self, x: usize, y: usize, pattern: &Pattern, rotation: isize) {
+    /// Grid index and new symbol of the cells `pattern` overwrites, i.e. all but its `ANYTHING`
+    /// cells.
+    fn written_cells(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+        pattern: &Pattern,
+        rotation: Transform,
+    ) -> Vec<(usize, char)> {
+        let rotated_seq = pattern.rotated(rotation);
+        let (width, height) = (rotated_seq.width, rotated_seq.height);
+
+        rotated_seq
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, &pattern_char)| pattern_char != ANYTHING)
+            .filter_map(|(i, &pattern_char)| {
+                let (px, py, pz) =
+                    self.window_cell(x, y, z, i % width, i / width % height, i / (width * height))?;
+                Some(((pz * self.height + py) * self.width + px, pattern_char))
+            })
+            .collect()
+    }
+
+    pub fn apply_pattern(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        pattern: &Pattern,
+        rotation: Transform,
+    ) {
         self.changes += 1;
 
-        let rotated_seq = pattern
-            .rotations
-            .iter()
-            .find(|&rotated_seq| rotated_seq.rotation == rotation)
-            .unwrap();
+        let rotated_seq = pattern.rotated(rotation);
+        let (width, height) = (rotated_seq.width, rotated_seq.height);
 
         for (i, &pattern_char) in rotated_seq.data.iter().enumerate() {
-            let px = i % rotated_seq.width;
-            let py = i / rotated_seq.width;
             if pattern_char != ANYTHING {
-                let index = (y + py) * self.width + (x + px);
-                self.grid[index] = pattern_char as u8;
+                // windows hanging off the grid don't write outside of it
+                let Some((px, py, pz)) =
+                    self.window_cell(x, y, z, i % width, i / width % height, i / (width * height))
+                else {
+                    continue;
+                };
+                let index = (pz * self.height + py) * self.width + px;
+                let symbol = self.alphabet.insert(pattern_char);
+                if self.grid[index] != symbol {
+                    self.grid[index] = symbol;
+                    self.changed_cells += 1;
+                    self.step_cells.push((px, py, pz));
+                }
             }
         }
     }
 
-    fn update_canonical_forms(
+    /// Brings the canonical forms and the matches of `rule` up to date with the `changed` cells.
+    fn update_around(
         &mut self,
         rule: &Rule,
-        x_range: &Range<usize>,
-        y_range: &Range<usize>,
+        cache: &mut MatchCache,
+        changed: &[(usize, usize, usize)],
     ) {
-        for pattern_index in 0..rule.patterns.len() {
-            let pattern_rule = &rule.patterns[pattern_index];
+        self.update_canonical_forms(changed);
+        self.refresh_matches(rule, cache, changed);
+    }
 
-            let Some(canonical_key) = pattern_rule.canonical_key else {
-                continue;
-            };
-            if !self.canonical_forms.contains_key(&canonical_key) {
-                unreachable!();
-            }
+    /// Brings the matches of `rule` up to date with the `changed` cells, once the canonical forms
+    /// are.
+    fn refresh_matches(
+        &mut self,
+        rule: &Rule,
+        cache: &mut MatchCache,
+        changed: &[(usize, usize, usize)],
+    ) {
+        // brings back the forms dropped for changing too much
+        self.precompute_canonical_forms(rule);
+
+        let sizes = rule
+            .patterns
+            .iter()
+            .map(|pattern_rule| {
+                let input = &pattern_rule.input;
+                (input.width, input.height, input.depth)
+            })
+            .collect::<Vec<_>>();
+        let cells = self.windows_covering(changed, &sizes);
+
+        // rescanning is cheaper once most of the grid is affected
+        let (anchor_width, anchor_height) = (self.anchor_width(), self.anchor_height());
+        if cells.len() * 4 >= anchor_width * anchor_height * self.anchor_length() {
+            *cache = self.compute_cache(rule);
+            return;
+        }
 
-            for dy in y_range.clone() {
-                for dx in x_range.clone() {
-                    let index = dy * self.width + dx;
-
-                    self.canonical_forms.get_mut(&canonical_key).unwrap()[index] =
-                        Self::compute_cell_canonical_form(
-                            &self.grid,
-                            self.width,
-                            self.height,
-                            dx,
-                            dy,
-                            canonical_key.0,
-                            canonical_key.1,
-                        );
+        for index in cells {
+            let (x, y, z) = (
+                index % anchor_width,
+                index / anchor_width % anchor_height,
+                index / (anchor_width * anchor_height),
+            );
+            cache.set_cell(x, y, z, self.cell_matches(rule, x, y, z));
+        }
+    }
+
+    /// Indices among the anchors, see [`MarkovJunior::margin`], of the corners closest to the
+    /// origin of the windows that cover any of the `changed` cells, for every
+    /// `(width, height, depth)` of `sizes` in both orientations of its layers.
+    fn windows_covering(
+        &self,
+        changed: &[(usize, usize, usize)],
+        sizes: &[(usize, usize, usize)],
+    ) -> Vec<usize> {
+        let (anchor_width, anchor_height) = (self.anchor_width(), self.anchor_height());
+        let z_margin = self.z_margin();
+        let mut cells = Vec::new();
+        for &(x, y, z) in changed {
+            for &(width, height, depth) in sizes {
+                for (width, height) in [(width, height), (height, width)] {
+                    for window_z in
+                        Self::window_starts(z, depth, self.length, self.wrap.z, z_margin)
+                    {
+                        for window_y in
+                            Self::window_starts(y, height, self.height, self.wrap.y, self.margin)
+                        {
+                            let row = window_z * anchor_height + window_y;
+                            cells.extend(
+                                Self::window_starts(x, width, self.width, self.wrap.x, self.margin)
+                                    .map(|window_x| row * anchor_width + window_x),
+                            );
+                        }
+                    }
                 }
             }
         }
+
+        cells.sort_unstable();
+        cells.dedup();
+        cells
     }
 
-    pub fn precompute_canonical_forms(&mut self, rule: &Rule) {
-        for pattern_rule in rule.patterns.iter() {
-            let Some(canonical_key) = pattern_rule.canonical_key else {
-                continue;
-            };
-            if self.canonical_forms.contains_key(&canonical_key) {
-                continue;
+    /// Starts along one axis of the windows of length `window` that contain `position`, on an
+    /// axis of length `size`. Starts before the axis lie in the `margin` past its end.
+    fn window_starts(
+        position: usize,
+        window: usize,
+        size: usize,
+        wraps: bool,
+        margin: usize,
+    ) -> impl Iterator<Item = usize> {
+        let (period, reach) = if wraps {
+            // windows longer than the axis wrap onto themselves, without adding new starts
+            (size, (window - 1).min(size - 1))
+        } else {
+            (size + margin, (window - 1).min(position + margin))
+        };
+        (position + period - reach..=position + period).map(move |start| start % period)
+    }
+
+    /// Recomputes the canonical forms of the windows that cover any of the `changed` cells, or
+    /// drops the forms of the keys for which most of the grid changed.
+    fn update_canonical_forms(&mut self, changed: &[(usize, usize, usize)]) {
+        // lent out so the grid can be read while the forms are written
+        let mut canonical_forms = std::mem::take(&mut self.canonical_forms);
+        let anchor_width = self.anchor_width();
+
+        canonical_forms.retain(|canonical_key, forms| {
+            let cells =
+                self.windows_covering(changed, &[(canonical_key.long, canonical_key.short, 1)]);
+            // computing them again once they are needed is cheaper
+            if cells.len() * 4 >= anchor_width * self.anchor_height() {
+                return false;
             }
 
-            let mut canonical_key_forms: Vec<RotatedSeq> =
-                Vec::with_capacity(self.width * self.height);
-
-            for y in 0..self.height {
-                for x in 0..self.width {
-                    canonical_key_forms.push(Self::compute_cell_canonical_form(
-                        &self.grid,
-                        self.width,
-                        self.height,
-                        x,
-                        y,
-                        canonical_key.0,
-                        canonical_key.1,
-                    ));
+            for index in cells {
+                let (x, y) = (index % anchor_width, index / anchor_width);
+                match forms {
+                    CanonicalForms::Packed(windows) => {
+                        windows[index] = self.pack_cell_windows(x, y, canonical_key)
+                    }
+                    CanonicalForms::Ids(ids) => {
+                        ids[index] = self.compute_cell_window_ids(x, y, canonical_key)
+                    }
                 }
             }
+            true
+        });
 
-            self.canonical_forms
-                .insert(canonical_key, canonical_key_forms);
-        }
+        self.canonical_forms = canonical_forms;
     }
 
-    pub fn compute_cache(
-        &self,
-        rule: &Rule,
-        x_range: &Range<usize>,
-        y_range: &Range<usize>,
-    ) -> BTreeMap<(usize, usize), Vec<PatternMatch>> {
-        y_range
-            .clone()
-            .flat_map(|y| x_range.clone().map(move |x| (x, y)))
-            .map(|(x, y)| {
-                let valid_patterns = rule
-                    .patterns
+    /// Widens the margin for the border cells of `rule` and, on flat grids, computes the forms of
+    /// its canonical keys that aren't kept yet. The forms of other keys are dropped, unless a
+    /// running markov node keeps matches that need them.
+    pub fn precompute_canonical_forms(&mut self, rule: &Rule) {
+        let margin = rule
+            .patterns
+            .iter()
+            .filter(|pattern_rule| pattern_rule.input.has_border)
+            .map(|pattern_rule| {
+                let input = &pattern_rule.input;
+                input.width.max(input.height).max(input.depth) - 1
+            })
+            .fold(self.margin, usize::max);
+        if margin > self.margin {
+            // every form is kept for the anchors, which the margin adds to
+            self.margin = margin;
+            self.canonical_forms.clear();
+        }
+        if self.length > 1 {
+            return;
+        }
+
+        let canonical_keys = Self::canonical_keys(rule);
+        // updating the forms of every key ever used would slow down every later step
+        self.canonical_forms.retain(|canonical_key, _| {
+            canonical_keys.contains(canonical_key)
+                || self
+                    .markov_logs
                     .iter()
-                    .enumerate()
-                    .filter_map(|(pattern_index, pattern_rule)| {
-                        let maybe_pattern_match = if pattern_rule.input.canonical_form.is_some() {
-                            self.pattern_fits_canonical(x, y, &pattern_rule.input)
-                        } else {
-                            self.pattern_fits(x, y, &pattern_rule.input)
-                        };
-
-                        maybe_pattern_match.map(|rotation| PatternMatch {
-                            x,
-                            y,
-                            probability: pattern_rule.probability,
-                            pattern_index,
-                            rotation,
-                        })
-                    })
-                    .collect::<Vec<_>>();
+                    .any(|log| log.canonical_keys.contains(canonical_key))
+        });
 
-                ((x, y), valid_patterns)
-            })
+        for canonical_key in canonical_keys {
+            if self.canonical_forms.contains_key(canonical_key) {
+                continue;
+            }
+
+            let (x_range, y_range) = (0..self.anchor_width(), 0..self.anchor_height());
+            let forms = if canonical_key.is_packed() {
+                CanonicalForms::Packed(Self::map_cells(&x_range, &y_range, |x, y| {
+                    self.pack_cell_windows(x, y, canonical_key)
+                }))
+            } else {
+                CanonicalForms::Ids(Self::map_cells(&x_range, &y_range, |x, y| {
+                    self.compute_cell_window_ids(x, y, canonical_key)
+                }))
+            };
+            self.canonical_forms.insert(canonical_key.clone(), forms);
+        }
+    }
+
+    fn canonical_keys(rule: &Rule) -> BTreeSet<&CanonicalKey> {
+        rule.patterns
+            .iter()
+            .filter_map(|pattern_rule| pattern_rule.canonical_key.as_ref())
             .collect()
     }
 
-    pub fn print_grid(&self) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                print!("{}", self.grid[y * self.width + x] as char);
-            }
-            println!();
+    /// Finds every match of `rule` on the grid, including the windows starting in the margin.
+    pub fn compute_cache(&self, rule: &Rule) -> MatchCache {
+        let (width, height, length) = (
+            self.anchor_width(),
+            self.anchor_height(),
+            self.anchor_length(),
+        );
+        let mut cache = MatchCache::new(width, height, length, rule.patterns.len());
+        // the rows of every layer, one layer after another
+        let cells = Self::map_cells(&(0..width), &(0..height * length), |x, row| {
+            self.cell_matches(rule, x, row % height, row / height)
+                .collect::<Vec<_>>()
+        });
+
+        for (index, matches) in cells.into_iter().enumerate() {
+            let row = index / width;
+            cache.set_cell(index % width, row % height, row / height, matches);
         }
+        cache
     }
 
-    pub fn print_rule(rule: &Rule, maybe_changes: Option<usize>) {
-        if let Some(changes) = maybe_changes {
-            println!(
-                "Rule kind: {:?}, steps: {:?}, changes: {}",
-                rule.kind, rule.steps, changes
-            );
-        } else {
-            println!("Rule kind: {:?}, steps: {:?}", rule.kind, rule.steps);
-        }
-        for pattern_rule in rule.patterns.iter() {
-            println!(
-                "{} => {}",
-                pattern_rule.input.line, pattern_rule.output.line,
-            );
+    /// Matches of the patterns of `rule` at `x`, `y`, `z`, in pattern order.
+    pub fn cell_matches<'a>(
+        &'a self,
+        rule: &'a Rule,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> impl Iterator<Item = PatternMatch> + 'a {
+        rule.patterns
+            .iter()
+            .enumerate()
+            .filter_map(move |(pattern_index, pattern_rule)| {
+                let maybe_pattern_match =
+                    if self.length == 1 && pattern_rule.canonical_key.is_some() {
+                        self.pattern_fits_canonical(x, y, &pattern_rule.input)
+                    } else {
+                        self.pattern_fits(x, y, z, &pattern_rule.input)
+                    };
+
+                maybe_pattern_match.map(|rotation| PatternMatch {
+                    x,
+                    y,
+                    z,
+                    probability: pattern_rule.probability,
+                    pattern_index,
+                    rotation,
+                })
+            })
+    }
+
+    /// Maps every cell of the ranges, row by row. With the `parallel` feature, large ranges are
+    /// split into chunks of `PARALLEL_ROWS` rows mapped on the rayon thread pool; the results
+    /// keep their row order, so they are the same as the serial ones.
+    fn map_cells<T: Send>(
+        x_range: &Range<usize>,
+        y_range: &Range<usize>,
+        f: impl Fn(usize, usize) -> T + Send + Sync,
+    ) -> Vec<T> {
+        let width = x_range.len();
+        let cells = 0..width * y_range.len();
+        let cell = |index: usize| f(x_range.start + index % width, y_range.start + index / width);
+
+        #[cfg(feature = "parallel")]
+        if y_range.len() > PARALLEL_ROWS {
+            return cells
+                .into_par_iter()
+                .with_min_len(width * PARALLEL_ROWS)
+                .map(cell)
+                .collect();
         }
+
+        cells.map(cell).collect()
     }
 
-    pub fn try_log_to_output_file(&self) {
-        let Some(filename) = self.maybe_output_file else {
-            return;
-        };
+    pub fn print_grid(&self) {
+        self.write_grid(&mut io::stdout().lock())
+            .expect("Failed to write to stdout");
+    }
 
-        let mut file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(filename)
-            .expect("Failed to open file");
-
-        for y in 0..self.height {
-            for x in 0..self.width {
-                write!(file, "{}", self.grid[y * self.width + x] as char)
-                    .expect("Failed to write to file");
+    /// Writes the grid one row per line, with an empty line between layers.
+    pub fn write_grid(&self, out: &mut impl Write) -> io::Result<()> {
+        for (z, layer) in self.grid.chunks(self.width * self.height).enumerate() {
+            if z > 0 {
+                writeln!(out)?;
+            }
+            for row in layer.chunks(self.width) {
+                for &index in row {
+                    write!(out, "{}", self.alphabet.symbol(index))?;
+                }
+                writeln!(out)?;
             }
-            writeln!(file).expect("Failed to write to file");
         }
+        Ok(())
     }
 
-    fn trye_log_command(&self) {
-        let Some(log_cmd) = self.maybe_log_cmd else {
-            return;
-        };
-
-        std::process::Command::new("sh")
-            .arg("-c")
-            .arg(log_cmd)
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .expect("Failed to execute shell command");
+    /// Columns of top left corners that matches and canonical forms are kept for: the grid and,
+    /// unless it wraps around, the margin.
+    fn anchor_width(&self) -> usize {
+        self.width + if self.wrap.x { 0 } else { self.margin }
     }
 
-    fn cached_patterns(cache: &BTreeMap<(usize, usize), Vec<PatternMatch>>) -> Vec<&PatternMatch> {
-        cache
-            .iter()
-            .flat_map(|(_k, pattern_matches)| pattern_matches)
-            .collect()
+    /// Rows of top left corners, like [`MarkovJunior::anchor_width`].
+    fn anchor_height(&self) -> usize {
+        self.height + if self.wrap.y { 0 } else { self.margin }
     }
 
-    fn x_range(x: usize, size: usize, grid_size: usize) -> Range<usize> {
-        let from_x = x.saturating_sub(size - 1);
-        let to_x = std::cmp::min(x + size, grid_size);
+    /// Layers of corners, like [`MarkovJunior::anchor_width`].
+    fn anchor_length(&self) -> usize {
+        self.length + if self.wrap.z { 0 } else { self.z_margin() }
+    }
 
-        from_x..to_x
+    /// Margin along z, which flat grids do without as their patterns are flat too.
+    fn z_margin(&self) -> usize {
+        if self.length == 1 {
+            0
+        } else {
+            self.margin
+        }
     }
 
-    fn compute_cell_canonical_form(
-        grid: &[u8],
+    /// Whether a `width` by `height` by `depth` window with its corner closest to the origin at
+    /// `x`, `y`, `z` lies on the grid, once wrapped around.
+    fn window_fits(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
         width: usize,
         height: usize,
+        depth: usize,
+    ) -> bool {
+        (self.wrap.x || x + width <= self.width)
+            && (self.wrap.y || y + height <= self.height)
+            && (self.wrap.z || z + depth <= self.length)
+    }
+
+    /// Grid cell `px`, `py`, `pz` away from the corner `x`, `y`, `z` of a window, wrapped around
+    /// if need be, or `None` outside of the grid.
+    fn window_cell(
+        &self,
         x: usize,
         y: usize,
-        pattern_width: usize,
-        pattern_height: usize,
-    ) -> RotatedSeq {
-        let mut data = Vec::with_capacity(pattern_width * pattern_height);
-        for py in 0..pattern_height {
-            for px in 0..pattern_width {
-                let gx = x + px;
-                let gy = y + py;
-                if gx < width && gy < height {
-                    data.push(grid[gy * width + gx] as char);
-                } else {
-                    data.push(NOTHING);
+        z: usize,
+        px: usize,
+        py: usize,
+        pz: usize,
+    ) -> Option<(usize, usize, usize)> {
+        let z = if self.length == 1 {
+            // flat grids have a single layer, which is worth not looking up for every cell
+            (z + pz == 0).then_some(0)?
+        } else {
+            Self::window_cell_along(z, pz, self.length, self.wrap.z, self.margin)?
+        };
+        Some((
+            Self::window_cell_along(x, px, self.width, self.wrap.x, self.margin)?,
+            Self::window_cell_along(y, py, self.height, self.wrap.y, self.margin)?,
+            z,
+        ))
+    }
+
+    fn window_cell_along(
+        start: usize,
+        offset: usize,
+        size: usize,
+        wraps: bool,
+        margin: usize,
+    ) -> Option<usize> {
+        let position = if wraps {
+            (start + offset) % size
+        } else if start < size {
+            start + offset
+        } else {
+            // windows starting in the margin stand for the ones starting before the grid
+            (start + offset).checked_sub(size + margin)?
+        };
+        (position < size).then_some(position)
+    }
+
+    /// Symbol of the cell `px`, `py`, `pz` away from the corner `x`, `y`, `z` of a window, or
+    /// `NOTHING` outside of the grid.
+    fn symbol_at(&self, x: usize, y: usize, z: usize, px: usize, py: usize, pz: usize) -> char {
+        match self.window_cell(x, y, z, px, py, pz) {
+            Some((x, y, z)) => self
+                .alphabet
+                .symbol(self.grid[(z * self.height + y) * self.width + x]),
+            None => NOTHING,
+        }
+    }
+
+    /// Symbols of the `long` by `short` and the `short` by `long` window whose top left corner is
+    /// at `x`, `y`, packed by [`pack_symbols`].
+    fn pack_cell_windows(&self, x: usize, y: usize, canonical_key: &CanonicalKey) -> [u128; 2] {
+        let CanonicalKey { long, short } = *canonical_key;
+
+        [(long, short), (short, long)].map(|(width, height)| {
+            // most windows need neither wrapping around nor the margin
+            if x + width <= self.width && y + height <= self.height {
+                pack_symbols((0..width * height).map(|i| {
+                    let index = (y + i / width) * self.width + x + i % width;
+                    self.alphabet.symbol(self.grid[index])
+                }))
+            } else {
+                pack_symbols(
+                    (0..width * height).map(|i| self.symbol_at(x, y, 0, i % width, i / width, 0)),
+                )
+            }
+        })
+    }
+
+    /// Canonical ids of the `long` by `short` and the `short` by `long` window whose top left
+    /// corner is at `x`, `y`, found by comparing their rotations in place.
+    fn compute_cell_window_ids(
+        &self,
+        x: usize,
+        y: usize,
+        canonical_key: &CanonicalKey,
+    ) -> [CanonicalId; 2] {
+        use Transform::*;
+
+        let CanonicalKey { long, short } = *canonical_key;
+
+        // rotations turning each window into the `long` by `short` orientation
+        [
+            (
+                (long, short),
+                [Identity, Rotate180, Mirror, MirrorRotate180],
+            ),
+            (
+                (short, long),
+                [Rotate90, Rotate270, MirrorRotate90, MirrorRotate270],
+            ),
+        ]
+        .map(|((width, height), mut rotations)| {
+            let symbol = |rotation: Transform, i: usize| {
+                let (px, py) = rotation.source(width, height, i % long, i / long);
+                self.symbol_at(x, y, 0, px, py, 0)
+            };
+
+            // the first of the smallest rotations, like `Pattern::compute_canonical_form_and_rotations`,
+            // narrowed down one position at a time while several of them are tied
+            let mut tied = rotations.len();
+            for i in 0..long * short {
+                if tied == 1 {
+                    break;
                 }
+                let symbols = rotations.map(|rotation| symbol(rotation, i));
+                let smallest = *symbols[..tied].iter().min().unwrap();
+                let mut kept = 0;
+                for candidate in 0..tied {
+                    if symbols[candidate] == smallest {
+                        rotations[kept] = rotations[candidate];
+                        kept += 1;
+                    }
+                }
+                tied = kept;
             }
-        }
-        Pattern::compute_canonical_form_and_rotations(&data, pattern_width, pattern_height, false)
-            .0
-            .unwrap()
+
+            let rotation = rotations[0];
+            CanonicalId::new((0..long * short).map(|i| symbol(rotation, i)), rotation)
+        })
     }
 }
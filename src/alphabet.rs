@@ -0,0 +1,100 @@
+use crate::*;
+use std::str::FromStr;
+
+/// Most symbols an alphabet can hold, since grid cells store symbol indices as `u8`.
+pub const MAX_SYMBOLS: usize = u8::MAX as usize + 1;
+
+/// Symbols the grid of a model can hold, in the order they are declared by the root `values`
+/// attribute. Also the symbol table of the grid, whose cells store indices into it.
+#[derive(Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Alphabet {
+    symbols: Vec<char>,
+}
+
+impl Alphabet {
+    /// Alphabet of the distinct `symbols`, in order of first appearance.
+    pub fn from_symbols(symbols: impl IntoIterator<Item = char>) -> Self {
+        let mut alphabet = Alphabet::default();
+        for symbol in symbols {
+            alphabet.insert(symbol);
+        }
+        alphabet
+    }
+
+    /// Index of `symbol`, appending it first if it is not part of the alphabet yet.
+    pub fn insert(&mut self, symbol: char) -> u8 {
+        let index = match self.index_of(symbol) {
+            Some(index) => index,
+            None => {
+                assert!(
+                    self.len() < MAX_SYMBOLS,
+                    "an alphabet holds at most {MAX_SYMBOLS} symbols"
+                );
+                self.symbols.push(symbol);
+                self.len() - 1
+            }
+        };
+        index as u8
+    }
+
+    pub fn symbol(&self, index: u8) -> char {
+        self.symbols[index as usize]
+    }
+
+    pub fn symbols(&self) -> &[char] {
+        &self.symbols
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn contains(&self, symbol: char) -> bool {
+        self.symbols.contains(&symbol)
+    }
+
+    pub fn index_of(&self, symbol: char) -> Option<usize> {
+        self.symbols.iter().position(|&s| s == symbol)
+    }
+
+    /// Valid symbol to suggest in place of an unknown one: the same letter in another case if
+    /// there is one, otherwise the symbol whose lowercase form is nearest in code point order.
+    pub fn closest(&self, symbol: char) -> Option<char> {
+        let key = |c: char| c.to_lowercase().next().unwrap_or(c) as i64;
+
+        self.symbols
+            .iter()
+            .copied()
+            .min_by_key(|&s| (key(s) - key(symbol)).abs())
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut alphabet = Alphabet::default();
+
+        for symbol in s.chars() {
+            if is_reserved(symbol) {
+                return Err(format!("'{symbol}' is reserved"));
+            }
+            if alphabet.contains(symbol) {
+                return Err(format!("'{symbol}' is listed more than once"));
+            }
+            alphabet.symbols.push(symbol);
+        }
+
+        if alphabet.is_empty() {
+            return Err("expected at least one symbol".to_string());
+        }
+        if alphabet.len() > MAX_SYMBOLS {
+            return Err(format!("expected at most {MAX_SYMBOLS} symbols"));
+        }
+        Ok(alphabet)
+    }
+}
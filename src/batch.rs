@@ -0,0 +1,77 @@
+use crate::*;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Runs `model` to completion once per seed and hands every finished run to `on_run`, returning
+/// the results in the order of `seeds`. Runs happen in parallel with the `parallel` feature;
+/// since each one only depends on its seed, the results are the same for any number of threads.
+pub fn run_batch<T, F>(model: &Arc<Model>, seeds: &[u64], on_run: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(Run) -> T + Send + Sync,
+{
+    let run_seed = |&seed: &u64| {
+        let mut run = Run::new(model.clone(), Some(seed));
+        run.run();
+        on_run(run)
+    };
+
+    #[cfg(feature = "parallel")]
+    return seeds.par_iter().map(run_seed).collect();
+
+    #[cfg(not(feature = "parallel"))]
+    return seeds.iter().map(run_seed).collect();
+}
+
+/// Runs `model` once per seed and writes every grid to its [`batch_output_path`] in `dir`.
+pub fn write_batch(
+    model: &Arc<Model>,
+    name: &str,
+    seeds: &[u64],
+    dir: impl AsRef<Path>,
+) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+
+    run_batch(model, seeds, |run| {
+        let path = batch_output_path(dir, name, run.mj.seed);
+        let mut file = BufWriter::new(File::create(&path)?);
+        run.mj.write_grid(&mut file)?;
+        file.flush()?;
+        Ok(path)
+    })
+    .into_iter()
+    .collect()
+}
+
+pub fn batch_output_path(dir: &Path, name: &str, seed: u64) -> PathBuf {
+    dir.join(format!("{name}_{seed}.txt"))
+}
+
+/// Parses a half-open seed range like `100..200`, which has to hold at least one seed.
+pub fn parse_seed_range(s: &str) -> Result<Range<u64>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("expected a range like 0..100, got {s:?}"))?;
+    let parse = |bound: &str| {
+        bound
+            .parse::<u64>()
+            .map_err(|err| format!("invalid seed {bound:?}: {err}"))
+    };
+
+    let (start, end) = (parse(start)?, parse(end)?);
+    if end <= start {
+        return Err(format!(
+            "{s:?} holds no seed, its end has to be after its start"
+        ));
+    }
+    Ok(start..end)
+}
@@ -0,0 +1,160 @@
+use crate::*;
+
+/// Most cells of the windows whose symbols are packed into a `u128`, one byte per cell. Patterns
+/// of up to this many cells can have `ANYTHING` cells.
+pub const PACKED_CELLS: usize = 16;
+
+/// Which precomputed forms of the grid a pattern is matched against: the forms of the `long` by
+/// `short` window at every cell and of its `short` by `long` counterpart. Patterns with the same
+/// key share them.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct CanonicalKey {
+    pub long: usize,
+    pub short: usize,
+}
+
+impl CanonicalKey {
+    /// Key of a pattern that can take the fast path: patterns of more than one cell made of
+    /// symbols, border cells and, in windows of at most [`PACKED_CELLS`] cells, `ANYTHING`.
+    pub fn of(cells: &[PatternCell], width: usize, height: usize) -> Option<Self> {
+        let canonical_key = CanonicalKey {
+            long: width.max(height),
+            short: width.min(height),
+        };
+        let kept = |cell: &PatternCell| match cell {
+            PatternCell::Symbol(_) | PatternCell::Border => true,
+            PatternCell::Anything => canonical_key.is_packed(),
+            PatternCell::OneOf(_) | PatternCell::NoneOf(_) => false,
+        };
+
+        let some_fixed = cells.iter().any(|cell| *cell != PatternCell::Anything);
+        (width * height > 1 && some_fixed && cells.iter().all(kept)).then_some(canonical_key)
+    }
+
+    /// Whether the grid keeps the packed symbols of the windows, rather than their canonical ids.
+    pub fn is_packed(&self) -> bool {
+        self.long * self.short <= PACKED_CELLS
+    }
+}
+
+/// Forms of the grid for one [`CanonicalKey`], at every anchor, for the `long` by `short` and
+/// the `short` by `long` window.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CanonicalForms {
+    /// Symbols of the windows packed by [`pack_symbols`], for packed keys.
+    Packed(Vec<[u128; 2]>),
+    /// Canonical ids of the windows, for larger keys.
+    Ids(Vec<[CanonicalId; 2]>),
+}
+
+/// Byte standing for `symbol` in packed windows, 0 for `NOTHING`. Symbols below U+00FF each get
+/// their own byte, while the others share bytes with them.
+pub fn symbol_code(symbol: char) -> u8 {
+    match symbol {
+        NOTHING => 0,
+        _ => (symbol as u32 % 255) as u8 + 1,
+    }
+}
+
+/// Whether no other symbol of [`symbol_code`] below U+00FF shares the byte of `symbol`.
+pub fn has_own_code(symbol: char) -> bool {
+    (symbol as u32) < 255
+}
+
+/// Window packed one byte per cell, row by row, starting from the lowest byte.
+pub fn pack_symbols(symbols: impl IntoIterator<Item = char>) -> u128 {
+    symbols
+        .into_iter()
+        .enumerate()
+        .fold(0, |packed, (i, symbol)| {
+            packed | ((symbol_code(symbol) as u128) << (8 * i))
+        })
+}
+
+/// One of the rotations of a pattern with a packed key, matched against the packed symbols of
+/// the window of its orientation.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PackedRotation {
+    pub rotation: Transform,
+    /// 0 if the rotation is `long` wide, 1 if it is `short` wide.
+    pub orientation: usize,
+    /// Bytes of the cells that have to hold a given symbol, or be outside of the grid.
+    pub mask: u128,
+    /// Packed symbols of those cells, 0 elsewhere.
+    pub symbols: u128,
+    /// Bytes of the `ANYTHING` cells, which have to be on the grid.
+    pub anything: u128,
+    /// Whether every symbol has its own code, so that matching bytes are matching symbols.
+    pub exact: bool,
+}
+
+impl PackedRotation {
+    pub fn new(rotated_seq: &RotatedSeq<PatternCell>, canonical_key: &CanonicalKey) -> Self {
+        let (mut mask, mut symbols, mut anything) = (0, 0, 0);
+        let mut exact = true;
+        for (i, cell) in rotated_seq.data.iter().enumerate() {
+            let byte = 0xff << (8 * i);
+            match cell {
+                PatternCell::Symbol(symbol) => {
+                    mask |= byte;
+                    symbols |= (symbol_code(*symbol) as u128) << (8 * i);
+                    exact &= has_own_code(*symbol);
+                }
+                PatternCell::Border => mask |= byte,
+                PatternCell::Anything => anything |= byte,
+                PatternCell::OneOf(_) | PatternCell::NoneOf(_) => {
+                    unreachable!("patterns with character classes have no canonical key")
+                }
+            }
+        }
+
+        PackedRotation {
+            rotation: rotated_seq.rotation,
+            orientation: usize::from(rotated_seq.width != canonical_key.long),
+            mask,
+            symbols,
+            anything,
+            exact,
+        }
+    }
+
+    /// Whether the cells of the window `packed` match the bytes of the rotation.
+    pub fn fits(&self, packed: u128) -> bool {
+        const LOW_BITS: u128 = u128::MAX / 0xff;
+        const HIGH_BITS: u128 = LOW_BITS << 7;
+
+        // cells outside of the grid are the zero bytes, which `ANYTHING` cells must not be
+        let kept = packed | !self.anything;
+        let has_zero_byte = kept.wrapping_sub(LOW_BITS) & !kept & HIGH_BITS != 0;
+        packed & self.mask == self.symbols && !has_zero_byte
+    }
+}
+
+/// Compact stand-in for a canonical form: a hash of its data together with its rotation. Different
+/// hashes mean different data; equal ones almost always mean equal data.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct CanonicalId {
+    pub hash: u64,
+    pub rotation: Transform,
+}
+
+impl CanonicalId {
+    pub fn new(data: impl IntoIterator<Item = char>, rotation: Transform) -> Self {
+        CanonicalId {
+            hash: hash_symbols(data),
+            rotation,
+        }
+    }
+
+    pub fn of(canonical_form: &RotatedSeq) -> Self {
+        Self::new(canonical_form.data.iter().copied(), canonical_form.rotation)
+    }
+}
+
+/// Hash of a sequence of symbols, cheap enough to run for every window around every change.
+pub fn hash_symbols(symbols: impl IntoIterator<Item = char>) -> u64 {
+    symbols.into_iter().fold(0, |hash, symbol| {
+        let hash = (hash ^ symbol as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        hash ^ (hash >> 32)
+    })
+}
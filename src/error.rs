@@ -0,0 +1,235 @@
+use crate::*;
+use roxmltree::Node;
+pub use roxmltree::TextPos;
+use std::{fmt, path::Path};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ModelError {
+    /// The model file could not be read.
+    Io { path: String, message: String },
+    /// No model file or built-in model with this name.
+    UnknownModel { name: String },
+    /// The document is not well-formed XML.
+    Xml { position: TextPos, message: String },
+    /// An element whose tag is not a known node kind.
+    UnknownNode {
+        position: TextPos,
+        path: String,
+        tag: String,
+    },
+    /// A required attribute is absent.
+    MissingAttribute {
+        position: TextPos,
+        path: String,
+        attribute: String,
+    },
+    /// An attribute is present, but its value can't be used.
+    InvalidAttribute {
+        position: TextPos,
+        path: String,
+        attribute: String,
+        value: String,
+        reason: String,
+    },
+    /// A symbol that is not part of the root `values`.
+    UnknownSymbol {
+        position: TextPos,
+        path: String,
+        attribute: String,
+        symbol: char,
+        suggestion: Option<char>,
+    },
+    /// A setting of [`ModelOptions`] overriding an attribute can't be used, e.g. `--size 0`.
+    InvalidOverride {
+        attribute: String,
+        value: String,
+        reason: String,
+    },
+}
+
+impl ModelError {
+    pub fn io(path: &Path, error: std::io::Error) -> Self {
+        ModelError::Io {
+            path: path.display().to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn unknown_node(node: &Node) -> Self {
+        ModelError::UnknownNode {
+            position: node_position(node),
+            path: node_path(node),
+            tag: node.tag_name().name().to_string(),
+        }
+    }
+
+    pub fn missing_attribute(node: &Node, attribute: &str) -> Self {
+        ModelError::MissingAttribute {
+            position: node_position(node),
+            path: node_path(node),
+            attribute: attribute.to_string(),
+        }
+    }
+
+    pub fn invalid_attribute(node: &Node, attribute: &str, reason: impl ToString) -> Self {
+        ModelError::InvalidAttribute {
+            position: attribute_position(node, attribute),
+            path: node_path(node),
+            attribute: attribute.to_string(),
+            value: node.attribute(attribute).unwrap_or_default().to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn unknown_symbol(node: &Node, attribute: &str, symbol: char, alphabet: &Alphabet) -> Self {
+        ModelError::UnknownSymbol {
+            position: attribute_position(node, attribute),
+            path: node_path(node),
+            attribute: attribute.to_string(),
+            symbol,
+            suggestion: alphabet.closest(symbol),
+        }
+    }
+
+    pub fn invalid_override(attribute: &str, value: impl ToString, reason: impl ToString) -> Self {
+        ModelError::InvalidOverride {
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn position(&self) -> Option<TextPos> {
+        match self {
+            ModelError::Io { .. }
+            | ModelError::UnknownModel { .. }
+            | ModelError::InvalidOverride { .. } => None,
+            ModelError::Xml { position, .. }
+            | ModelError::UnknownNode { position, .. }
+            | ModelError::MissingAttribute { position, .. }
+            | ModelError::InvalidAttribute { position, .. }
+            | ModelError::UnknownSymbol { position, .. } => Some(*position),
+        }
+    }
+
+    /// Path of the offending node, e.g. `/sequence/one[2]/rule[1]`.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            ModelError::Io { .. }
+            | ModelError::UnknownModel { .. }
+            | ModelError::Xml { .. }
+            | ModelError::InvalidOverride { .. } => None,
+            ModelError::UnknownNode { path, .. }
+            | ModelError::MissingAttribute { path, .. }
+            | ModelError::InvalidAttribute { path, .. }
+            | ModelError::UnknownSymbol { path, .. } => Some(path),
+        }
+    }
+
+    /// Renders the error as a compiler-style diagnostic pointing into `source`.
+    pub fn diagnostic(&self, source: &str, source_name: &str) -> String {
+        let Some(TextPos { row, col }) = self.position() else {
+            return format!("error: {self}\n");
+        };
+        let gutter = row.to_string().len();
+        let line = source.lines().nth(row as usize - 1).unwrap_or_default();
+        let caret_offset = line
+            .chars()
+            .take(col as usize - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        let mut out = format!("error: {self}\n");
+        out += &format!("{:gutter$}--> {source_name}:{row}:{col}\n", "");
+        out += &format!("{:gutter$} |\n", "");
+        out += &format!("{row} | {line}\n");
+        out += &format!("{:gutter$} | {caret_offset}^\n", "");
+        if let Some(path) = self.path() {
+            out += &format!("{:gutter$} = node: {path}\n", "");
+        }
+        out
+    }
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Io { path, message } => write!(f, "failed to read {path}: {message}"),
+            ModelError::UnknownModel { name } => write!(f, "unknown model {name:?}"),
+            ModelError::Xml { message, .. } => write!(f, "malformed XML: {message}"),
+            ModelError::UnknownNode { tag, .. } => write!(f, "unknown node <{tag}>"),
+            ModelError::MissingAttribute { attribute, .. } => {
+                write!(f, "missing required attribute `{attribute}`")
+            }
+            ModelError::InvalidAttribute {
+                attribute,
+                value,
+                reason,
+                ..
+            } => write!(f, "invalid value {value:?} for `{attribute}`: {reason}"),
+            ModelError::UnknownSymbol {
+                attribute,
+                symbol,
+                suggestion,
+                ..
+            } => {
+                write!(f, "unknown symbol '{symbol}' in `{attribute}`")?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean '{suggestion}'?"),
+                    None => Ok(()),
+                }
+            }
+            ModelError::InvalidOverride {
+                attribute,
+                value,
+                reason,
+            } => write!(
+                f,
+                "invalid value {value:?} overriding `{attribute}`: {reason}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl From<roxmltree::Error> for ModelError {
+    fn from(error: roxmltree::Error) -> Self {
+        ModelError::Xml {
+            position: error.pos(),
+            message: error.to_string(),
+        }
+    }
+}
+
+fn node_position(node: &Node) -> TextPos {
+    node.document().text_pos_at(node.range().start)
+}
+
+fn attribute_position(node: &Node, attribute: &str) -> TextPos {
+    node.attribute_node(attribute)
+        .map(|attr| node.document().text_pos_at(attr.position()))
+        .unwrap_or_else(|| node_position(node))
+}
+
+fn node_path(node: &Node) -> String {
+    let mut segments = node
+        .ancestors()
+        .filter(|n| n.is_element())
+        .map(|n| {
+            let name = n.tag_name().name();
+            if n.parent_element().is_none() {
+                return name.to_string();
+            }
+            // `prev_siblings` starts at the node itself, so the count is 1-based
+            let index = n
+                .prev_siblings()
+                .filter(|s| s.is_element() && s.tag_name().name() == name)
+                .count();
+            format!("{name}[{index}]")
+        })
+        .collect::<Vec<_>>();
+    segments.reverse();
+
+    format!("/{}", segments.join("/"))
+}
@@ -0,0 +1,418 @@
+use crate::*;
+use std::sync::Arc;
+
+/// Outcome of a single [`Interpreter::step`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StepResult {
+    /// Path of child indices from the root to the rule that fired, see [`Sequence::node`].
+    /// `None` once finished.
+    pub node: Option<Vec<usize>>,
+    /// Number of cells whose symbol changed.
+    pub changes: usize,
+    /// Set once no node can change the grid anymore.
+    pub finished: bool,
+}
+
+/// Progress of a node that is currently running.
+enum Frame {
+    Sequence {
+        path: Vec<usize>,
+        pass: usize,
+        child: usize,
+        pass_change: bool,
+        any_change: bool,
+    },
+    Markov {
+        path: Vec<usize>,
+        step: usize,
+        child: usize,
+        steps_left: Vec<Option<usize>>,
+        kept: Vec<Option<KeptMatches>>,
+        any_change: bool,
+    },
+    Rule {
+        path: Vec<usize>,
+        step: usize,
+        cache: MatchCache,
+        any_change: bool,
+    },
+}
+
+impl Frame {
+    fn path(&self) -> &[usize] {
+        match self {
+            Frame::Sequence { path, .. }
+            | Frame::Markov { path, .. }
+            | Frame::Rule { path, .. } => path,
+        }
+    }
+}
+
+enum Action {
+    Enter(Vec<usize>),
+    Finish(bool),
+    Fired(Vec<usize>),
+    Continue,
+}
+
+/// The nodes of a tree that are currently running, innermost last. Both [`Interpreter`] and
+/// [`MarkovJunior::apply_sequence`] run node trees through it, one rule step at a time.
+pub(crate) struct Walker {
+    stack: Vec<Frame>,
+    /// Value of `mj.changes` when each frame of the stack was entered.
+    start_changes: Vec<usize>,
+    /// Whether the root runs as a markov node rather than as a sequence.
+    markov_root: bool,
+    /// Whether the root is the root of the run, which the observer hears about finishing.
+    is_run: bool,
+    /// Whether the root changed anything, once it is done.
+    any_change: bool,
+}
+
+impl Walker {
+    pub(crate) fn new(
+        mj: &mut MarkovJunior,
+        observer: &mut dyn Observer,
+        root: &Sequence,
+        markov_root: bool,
+        is_run: bool,
+    ) -> Self {
+        let mut walker = Walker {
+            stack: Vec::new(),
+            start_changes: Vec::new(),
+            markov_root,
+            is_run,
+            any_change: false,
+        };
+        walker.enter(mj, observer, root, Vec::new());
+        walker
+    }
+
+    /// Runs every remaining step, returning whether the root changed anything.
+    pub(crate) fn run(
+        mut self,
+        mj: &mut MarkovJunior,
+        observer: &mut dyn Observer,
+        root: &Sequence,
+    ) -> bool {
+        while !self.step(mj, observer, root).finished {}
+        self.any_change
+    }
+
+    fn is_finished(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    fn step(
+        &mut self,
+        mj: &mut MarkovJunior,
+        observer: &mut dyn Observer,
+        root: &Sequence,
+    ) -> StepResult {
+        let changed_cells = mj.changed_cells;
+        let grid_size = mj.grid.len();
+
+        loop {
+            let Some(frame) = self.stack.last_mut() else {
+                return StepResult {
+                    node: None,
+                    changes: 0,
+                    finished: true,
+                };
+            };
+
+            let action = match frame {
+                Frame::Sequence {
+                    path,
+                    pass,
+                    child,
+                    pass_change,
+                    any_change,
+                } => {
+                    let sequence = Self::sequence_at(root, path);
+
+                    if *pass == sequence.steps.unwrap_or(grid_size) {
+                        Action::Finish(*any_change)
+                    } else if *child < sequence.vec.len() {
+                        Action::Enter(Self::child_path(path, *child))
+                    } else {
+                        *any_change |= *pass_change;
+                        if *pass_change {
+                            *pass += 1;
+                            *child = 0;
+                            *pass_change = false;
+                            Action::Continue
+                        } else {
+                            Action::Finish(*any_change)
+                        }
+                    }
+                }
+                Frame::Markov {
+                    path,
+                    step,
+                    child,
+                    steps_left,
+                    kept,
+                    any_change,
+                } => {
+                    let markov = Self::sequence_at(root, path);
+
+                    // finished once no child changes anything in a step
+                    if *step == markov.steps.unwrap_or(grid_size * 16) || *child == markov.vec.len()
+                    {
+                        Action::Finish(*any_change)
+                    } else {
+                        match &markov.vec[*child] {
+                            RuleOrSequence::Rule(_) if steps_left[*child] == Some(0) => {
+                                *child += 1;
+                                Action::Continue
+                            }
+                            // rules advance a single step per activation
+                            RuleOrSequence::Rule(rule) => {
+                                let node = &markov.vec[*child];
+                                let rule_path = Self::child_path(path, *child);
+                                observer.on_node_start(mj, node, &rule_path);
+                                let prev_changes = mj.changes;
+                                let applied =
+                                    mj.apply_markov_rule_step(rule, kept, *child, observer);
+                                let changes = mj.changes - prev_changes;
+                                observer.on_node_end(mj, node, &rule_path, changes);
+
+                                if applied {
+                                    if let Some(steps_left) = steps_left[*child].as_mut() {
+                                        *steps_left -= 1;
+                                    }
+                                    let fired = rule_path;
+                                    *any_change = true;
+                                    *step += 1;
+                                    *child = 0;
+                                    Action::Fired(fired)
+                                } else {
+                                    *child += 1;
+                                    Action::Continue
+                                }
+                            }
+                            RuleOrSequence::Sequence(_) | RuleOrSequence::Markov(_) => {
+                                Action::Enter(Self::child_path(path, *child))
+                            }
+                        }
+                    }
+                }
+                Frame::Rule {
+                    path,
+                    step,
+                    cache,
+                    any_change,
+                } => {
+                    let Some(RuleOrSequence::Rule(rule)) = root.node(path) else {
+                        unreachable!();
+                    };
+
+                    if *step == rule.steps.unwrap_or(grid_size * 16) {
+                        Action::Finish(*any_change)
+                    } else if mj.apply_rule_step(rule, cache, observer) {
+                        *step += 1;
+                        *any_change = true;
+                        Action::Fired(path.clone())
+                    } else {
+                        Action::Finish(*any_change)
+                    }
+                }
+            };
+
+            match action {
+                Action::Enter(path) => self.enter(mj, observer, root, path),
+                Action::Finish(any_change) => self.finish(mj, observer, root, any_change),
+                Action::Fired(path) => {
+                    return StepResult {
+                        node: Some(path),
+                        changes: mj.changed_cells - changed_cells,
+                        finished: false,
+                    }
+                }
+                Action::Continue => {}
+            }
+        }
+    }
+
+    fn enter(
+        &mut self,
+        mj: &mut MarkovJunior,
+        observer: &mut dyn Observer,
+        root: &Sequence,
+        path: Vec<usize>,
+    ) {
+        let maybe_node = root.node(&path);
+        if let Some(node) = maybe_node {
+            observer.on_node_start(mj, node, &path);
+        }
+        self.start_changes.push(mj.changes);
+
+        let is_markov = match maybe_node {
+            None => self.markov_root,
+            Some(node) => matches!(node, RuleOrSequence::Markov(_)),
+        };
+        let frame = match maybe_node {
+            Some(RuleOrSequence::Rule(rule)) => Frame::Rule {
+                cache: mj.start_rule(rule),
+                path,
+                step: 0,
+                any_change: false,
+            },
+            _ if is_markov => {
+                let markov = Self::sequence_at(root, &path);
+                mj.start_markov();
+                Frame::Markov {
+                    steps_left: markov
+                        .vec
+                        .iter()
+                        .map(|rule_or_sequence| match rule_or_sequence {
+                            RuleOrSequence::Rule(rule) => rule.steps,
+                            RuleOrSequence::Sequence(_) | RuleOrSequence::Markov(_) => None,
+                        })
+                        .collect(),
+                    kept: markov.vec.iter().map(|_| None).collect(),
+                    path,
+                    step: 0,
+                    child: 0,
+                    any_change: false,
+                }
+            }
+            _ => Frame::Sequence {
+                path,
+                pass: 0,
+                child: 0,
+                pass_change: false,
+                any_change: false,
+            },
+        };
+
+        self.stack.push(frame);
+    }
+
+    /// Pops the running node and reports to its parent whether it changed anything.
+    fn finish(
+        &mut self,
+        mj: &mut MarkovJunior,
+        observer: &mut dyn Observer,
+        root: &Sequence,
+        changed: bool,
+    ) {
+        let frame = self.stack.pop().unwrap();
+        if let Frame::Markov { .. } = frame {
+            mj.finish_markov();
+        }
+        let changes = mj.changes - self.start_changes.pop().unwrap();
+        match root.node(frame.path()) {
+            Some(node) => observer.on_node_end(mj, node, frame.path(), changes),
+            None if self.is_run => observer.on_finish(mj),
+            None => {}
+        }
+
+        match self.stack.last_mut() {
+            Some(Frame::Sequence {
+                child, pass_change, ..
+            }) => {
+                *pass_change |= changed;
+                *child += 1;
+            }
+            // back to the first child after every change
+            Some(Frame::Markov {
+                step,
+                child,
+                any_change,
+                ..
+            }) => {
+                if changed {
+                    *any_change = true;
+                    *step += 1;
+                    *child = 0;
+                } else {
+                    *child += 1;
+                }
+            }
+            Some(Frame::Rule { .. }) => unreachable!(),
+            None => self.any_change = changed,
+        }
+    }
+
+    fn sequence_at<'s>(root: &'s Sequence, path: &[usize]) -> &'s Sequence {
+        match root.node(path) {
+            None => root,
+            Some(RuleOrSequence::Sequence(sequence) | RuleOrSequence::Markov(sequence)) => sequence,
+            Some(RuleOrSequence::Rule(_)) => unreachable!(),
+        }
+    }
+
+    fn child_path(path: &[usize], child: usize) -> Vec<usize> {
+        let mut child_path = path.to_vec();
+        child_path.push(child);
+        child_path
+    }
+}
+
+/// Runs a node tree one rule step at a time, so callers can pause, render and resume between
+/// steps. Takes the same steps as [`Run::run`], so the grid and the callbacks of the observer of
+/// the run are the same for the same seed.
+pub struct Interpreter {
+    mj: MarkovJunior,
+    model: Arc<Model>,
+    observer: Box<dyn Observer>,
+    walker: Walker,
+}
+
+impl Interpreter {
+    pub fn new(run: Run) -> Self {
+        let Run {
+            model,
+            mut mj,
+            mut observer,
+        } = run;
+        let walker = Walker::new(&mut mj, observer.as_mut(), &model.root, false, true);
+        Interpreter {
+            mj,
+            model,
+            observer,
+            walker,
+        }
+    }
+
+    /// The grid is only meant to be read between steps, since the running rule keeps its matches
+    /// cached.
+    pub fn mj(&self) -> &MarkovJunior {
+        &self.mj
+    }
+
+    pub fn root(&self) -> &Sequence {
+        &self.model.root
+    }
+
+    /// The run as far as it got.
+    pub fn into_run(self) -> Run {
+        Run {
+            model: self.model,
+            mj: self.mj,
+            observer: self.observer,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.walker.is_finished()
+    }
+
+    /// Runs until a rule changes the grid once, or until the whole tree is done.
+    pub fn step(&mut self) -> StepResult {
+        self.walker
+            .step(&mut self.mj, self.observer.as_mut(), &self.model.root)
+    }
+}
+
+impl Iterator for Interpreter {
+    type Item = StepResult;
+
+    /// Steps until finished, leaving out the final result that only reports being finished.
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.step();
+        (!result.finished).then_some(result)
+    }
+}
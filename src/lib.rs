@@ -18,8 +18,19 @@ macro_rules! use_modules {
 
 use_modules!(
     algo,
+    alphabet,
+    batch,
+    canonical,
+    error,
+    interpreter,
+    match_cache,
+    model,
     models,
+    observer,
     pattern,
     rule_sequence,
+    symmetry,
+    transform,
+    wrap,
     xml
 );
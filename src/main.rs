@@ -1,66 +1,152 @@
 use markov_junior::*;
-use std::env;
+use std::{env, sync::Arc};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let is_batch = args.get(1).is_some_and(|arg| arg == "batch");
     let mut maybe_seed = None;
     let mut maybe_size = None;
+    let mut maybe_width = None;
+    let mut maybe_height = None;
+    let mut maybe_length = None;
     let mut maybe_model = None;
+    let mut maybe_model_file = None;
+    let mut models_dir = MODELS_DIR.to_string();
     let mut maybe_output_file = None;
     let mut maybe_log_cmd = None;
+    let mut maybe_count = None;
+    let mut maybe_seeds = None;
+    let mut output_dir = "output".to_string();
 
-    let mut i = 1;
+    let mut i = if is_batch { 2 } else { 1 };
     while i < args.len() {
         match args[i].as_str() {
-            "--seed" => {
-                if i + 1 < args.len() {
-                    maybe_seed = args[i + 1].parse().ok();
-                    i += 1;
-                }
+            "--seed" if i + 1 < args.len() => {
+                maybe_seed = args[i + 1].parse().ok();
+                i += 1;
             }
-            "--size" => {
-                if i + 1 < args.len() {
-                    maybe_size = args[i + 1].parse::<usize>().ok();
-                    i += 1;
-                }
+            "--size" if i + 1 < args.len() => {
+                maybe_size = args[i + 1].parse::<usize>().ok();
+                i += 1;
             }
-            "--model" => {
-                if i + 1 < args.len() {
-                    maybe_model = args[i + 1].parse::<ModelKind>().ok();
-                    i += 1;
-                }
+            "--width" if i + 1 < args.len() => {
+                maybe_width = args[i + 1].parse::<usize>().ok();
+                i += 1;
             }
-            "--output" => {
-                if i + 1 < args.len() {
-                    maybe_output_file = Some(args[i + 1].clone());
-                    i += 1;
-                }
+            "--height" if i + 1 < args.len() => {
+                maybe_height = args[i + 1].parse::<usize>().ok();
+                i += 1;
             }
-            "--log_cmd" => {
-                if i + 1 < args.len() {
-                    maybe_log_cmd = Some(args[i + 1].clone());
-                    i += 1;
-                }
+            "--length" if i + 1 < args.len() => {
+                maybe_length = args[i + 1].parse::<usize>().ok();
+                i += 1;
+            }
+            "--model" if i + 1 < args.len() => {
+                maybe_model = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--model-file" if i + 1 < args.len() => {
+                maybe_model_file = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--models-dir" if i + 1 < args.len() => {
+                models_dir = args[i + 1].clone();
+                i += 1;
+            }
+            "--output" if i + 1 < args.len() => {
+                maybe_output_file = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--log_cmd" if i + 1 < args.len() => {
+                maybe_log_cmd = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "--count" if i + 1 < args.len() => {
+                maybe_count = args[i + 1].parse::<u64>().ok();
+                i += 1;
+            }
+            "--seeds" if i + 1 < args.len() => {
+                maybe_seeds = Some(
+                    parse_seed_range(&args[i + 1])
+                        .unwrap_or_else(|err| panic!("Error: --seeds: {err}")),
+                );
+                i += 1;
+            }
+            "--output-dir" if i + 1 < args.len() => {
+                output_dir = args[i + 1].clone();
+                i += 1;
             }
             _ => {}
         }
         i += 1;
     }
-    let model = maybe_model.expect("Error: --model argument is required");
-    let size = maybe_size.expect("Error: --size argument is required");
+    let (source_name, maybe_xml) = match (&maybe_model_file, &maybe_model) {
+        (Some(path), _) => (path.clone(), read_model_file(path)),
+        (None, Some(name)) => (name.clone(), find_model_xml(name, &models_dir)),
+        (None, None) => panic!("Error: --model or --model-file argument is required"),
+    };
+    let options = ModelOptions {
+        width: maybe_width.or(maybe_size),
+        height: maybe_height.or(maybe_size),
+        length: maybe_length,
+        ..Default::default()
+    };
 
-    let xml = model_xml(model, size);
-    let (mut mj, sequence) = parse_xml(&xml, maybe_seed);
+    let xml = maybe_xml.unwrap_or_else(|err| exit_with_diagnostic(err, "", &source_name));
+    let model = parse_model(&xml, &options)
+        .unwrap_or_else(|err| exit_with_diagnostic(err, &xml, &source_name));
 
-    mj.maybe_log_cmd = maybe_log_cmd.as_deref();
-    mj.maybe_output_file = maybe_output_file.as_deref();
+    if is_batch {
+        let seeds = match (maybe_seeds, maybe_count) {
+            (Some(seeds), _) => seeds,
+            (None, Some(count)) => {
+                let start = maybe_seed.unwrap_or(0);
+                start..start + count
+            }
+            (None, None) => panic!("Error: batch needs --count or --seeds"),
+        };
+        // outputs are named after the model, whether it comes from a name or a file
+        let name = match &maybe_model_file {
+            Some(path) => std::path::Path::new(path).file_stem().map_or_else(
+                || "model".to_string(),
+                |stem| stem.to_string_lossy().to_string(),
+            ),
+            None => source_name.clone(),
+        };
 
-    mj.apply_sequence(&sequence, true);
+        let paths = write_batch(
+            &Arc::new(model),
+            &name,
+            &seeds.collect::<Vec<_>>(),
+            &output_dir,
+        )
+        .unwrap_or_else(|err| panic!("Error: failed to write to {output_dir}: {err}"));
+        for path in paths {
+            println!("{}", path.display());
+        }
+        return;
+    }
+
+    let mut observers: Vec<Box<dyn Observer>> = vec![Box::new(PrintObserver::default())];
+    if let Some(path) = &maybe_output_file {
+        observers.push(Box::new(OutputFileObserver::new(path)));
+    }
+    if let Some(command) = maybe_log_cmd {
+        observers.push(Box::new(CommandObserver::new(command)));
+    }
+    let mut run = Run::new(Arc::new(model), maybe_seed).with_observer(observers);
+
+    run.run();
 
     if maybe_output_file.is_none() {
-        mj.print_grid();
+        run.mj.print_grid();
     }
 
-    println!("seed: {}", mj.seed);
-    println!("changes: {}", mj.changes);
+    println!("seed: {}", run.mj.seed);
+    println!("changes: {}", run.mj.changes);
+}
+
+fn exit_with_diagnostic(err: ModelError, source: &str, source_name: &str) -> ! {
+    eprint!("{}", err.diagnostic(source, source_name));
+    std::process::exit(1);
 }
@@ -0,0 +1,198 @@
+use crate::*;
+
+const NO_MATCH: usize = usize::MAX;
+
+/// Live matches of a rule, indexed by the cell and pattern they belong to, so that the matches
+/// around a change can be replaced without looking at the rest of the grid.
+///
+/// Every pattern matches a cell at most once. Removed matches are swapped with the last one, so
+/// the order of [`MatchCache::matches`] only depends on the order of the updates. The weights of
+/// the matches are summed per slot in a Fenwick tree for weighted sampling.
+#[derive(Debug)]
+pub struct MatchCache {
+    width: usize,
+    height: usize,
+    pattern_count: usize,
+    matches: Vec<PatternMatch>,
+    /// Position in `matches` of the match of every pattern at every cell, or `NO_MATCH`.
+    slots: Vec<usize>,
+    weights: WeightTree,
+    /// Number of matches with a weight above zero, which rounding errors of the sums can't blur.
+    weighted_count: usize,
+}
+
+impl MatchCache {
+    pub fn new(width: usize, height: usize, length: usize, pattern_count: usize) -> Self {
+        let slot_count = width * height * length * pattern_count;
+
+        MatchCache {
+            width,
+            height,
+            pattern_count,
+            matches: Vec::new(),
+            slots: vec![NO_MATCH; slot_count],
+            weights: WeightTree::new(slot_count),
+            weighted_count: 0,
+        }
+    }
+
+    pub fn matches(&self) -> &[PatternMatch] {
+        &self.matches
+    }
+
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Sum of the weights of all matches, up to rounding errors. Exactly zero if none of them
+    /// has a weight.
+    pub fn total_weight(&self) -> f64 {
+        if self.weighted_count == 0 {
+            return 0.0;
+        }
+        self.weights.total()
+    }
+
+    /// Match at `weight` along the matches ordered by slot, i.e. by cell and then by pattern, with
+    /// each one spanning its own weight. `weight` is meant to be below [`MatchCache::total_weight`];
+    /// past the matches, the last one with a weight is picked. `None` if no match has a weight.
+    pub fn weighted(&self, weight: f64) -> Option<&PatternMatch> {
+        // rounding errors can point past the last match, or at a slot without weight
+        let end = (self.weights.find(weight) + 1).min(self.slots.len());
+        self.slots[..end]
+            .iter()
+            .rev()
+            .filter(|&&position| position != NO_MATCH)
+            .map(|&position| &self.matches[position])
+            .find(|pattern_match| Self::weight(pattern_match) > 0.0)
+    }
+
+    /// Matches of the cell at `x`, `y`, `z`, in pattern order.
+    pub fn cell(&self, x: usize, y: usize, z: usize) -> impl Iterator<Item = &PatternMatch> {
+        let first_slot = self.slot(x, y, z, 0);
+        self.slots[first_slot..first_slot + self.pattern_count]
+            .iter()
+            .filter(|&&position| position != NO_MATCH)
+            .map(|&position| &self.matches[position])
+    }
+
+    /// Replaces the matches of the cell at `x`, `y`, `z`.
+    pub fn set_cell(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        matches: impl IntoIterator<Item = PatternMatch>,
+    ) {
+        let first_slot = self.slot(x, y, z, 0);
+        for slot in first_slot..first_slot + self.pattern_count {
+            self.remove(slot);
+        }
+
+        for pattern_match in matches {
+            debug_assert_eq!(
+                (pattern_match.x, pattern_match.y, pattern_match.z),
+                (x, y, z)
+            );
+            let slot = first_slot + pattern_match.pattern_index;
+            self.remove(slot);
+            self.slots[slot] = self.matches.len();
+            self.add_weight(slot, Self::weight(&pattern_match));
+            self.matches.push(pattern_match);
+        }
+    }
+
+    fn remove(&mut self, slot: usize) {
+        let position = std::mem::replace(&mut self.slots[slot], NO_MATCH);
+        if position == NO_MATCH {
+            return;
+        }
+
+        let removed = self.matches.swap_remove(position);
+        self.add_weight(slot, -Self::weight(&removed));
+        if let Some(moved) = self.matches.get(position) {
+            let moved_slot = self.slot(moved.x, moved.y, moved.z, moved.pattern_index);
+            self.slots[moved_slot] = position;
+        }
+    }
+
+    fn add_weight(&mut self, slot: usize, weight: f64) {
+        if weight > 0.0 {
+            self.weighted_count += 1;
+        } else if weight < 0.0 {
+            self.weighted_count -= 1;
+        }
+        self.weights.add(slot, weight);
+    }
+
+    fn weight(pattern_match: &PatternMatch) -> f64 {
+        let weight = pattern_match.probability.unwrap_or(DEFAULT_PROBABILITY) as f64;
+        debug_assert!(
+            weight.is_finite() && weight >= 0.0,
+            "invalid match weight {weight}"
+        );
+        weight
+    }
+
+    fn slot(&self, x: usize, y: usize, z: usize, pattern_index: usize) -> usize {
+        ((z * self.height + y) * self.width + x) * self.pattern_count + pattern_index
+    }
+}
+
+/// Fenwick tree of the weight of every slot, for summing and searching weights in logarithmic
+/// time.
+///
+/// The sums are rounded, so removing a match doesn't always take away exactly the weight adding
+/// it put in, and slots without a match can be left with a tiny weight.
+#[derive(Debug)]
+struct WeightTree {
+    /// `sums[i]` holds the weights of the `i & i.wrapping_neg()` slots up to slot `i - 1`.
+    sums: Vec<f64>,
+}
+
+impl WeightTree {
+    fn new(len: usize) -> Self {
+        WeightTree {
+            sums: vec![0.0; len + 1],
+        }
+    }
+
+    fn add(&mut self, slot: usize, weight: f64) {
+        let mut i = slot + 1;
+        while i < self.sums.len() {
+            self.sums[i] += weight;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn total(&self) -> f64 {
+        let mut total = 0.0;
+        let mut i = self.sums.len() - 1;
+        while i > 0 {
+            total += self.sums[i];
+            i -= i & i.wrapping_neg();
+        }
+        total
+    }
+
+    /// First slot whose weights up to and including itself exceed `weight`.
+    fn find(&self, mut weight: f64) -> usize {
+        let len = self.sums.len() - 1;
+        let mut slot = 0;
+        let mut step = if len == 0 { 0 } else { 1 << len.ilog2() };
+
+        while step > 0 {
+            let next = slot + step;
+            if next <= len && self.sums[next] <= weight {
+                slot = next;
+                weight -= self.sums[next];
+            }
+            step >>= 1;
+        }
+        slot
+    }
+}
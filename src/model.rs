@@ -0,0 +1,69 @@
+use crate::*;
+use std::sync::Arc;
+
+/// A parsed model: the node tree with its compiled patterns and everything needed to set up the
+/// grid. Never changes once parsed, so one model can be shared by any number of [`Run`]s, e.g.
+/// across threads through an `Arc`.
+#[derive(Debug)]
+pub struct Model {
+    pub root: Sequence,
+    pub width: usize,
+    pub height: usize,
+    /// Number of layers, 1 for flat grids.
+    pub length: usize,
+    pub fill: char,
+    pub alphabet: Alphabet,
+    /// Axes along which the grid wraps around, from `periodic` or `wrap` on the root.
+    pub wrap: Wrap,
+    /// Whether the second symbol of the alphabet starts in the centre cell.
+    pub origin: bool,
+    /// Initial `(x, y, z, symbol)` placements, applied after `origin`.
+    pub puts: Vec<(usize, usize, usize, char)>,
+}
+
+impl Model {
+    /// Grid and run state of a fresh run, before the first node runs.
+    pub fn initial_state(&self, seed: Option<u64>) -> MarkovJunior {
+        let mut mj = MarkovJunior::new_3d(self.fill, self.width, self.height, self.length, seed)
+            .with_alphabet(self.alphabet.clone())
+            .with_wrap(self.wrap);
+        if self.origin {
+            mj.put_origin();
+        }
+        for &(x, y, z, symbol) in &self.puts {
+            mj.put(x, y, z, symbol);
+        }
+        mj
+    }
+}
+
+/// One generation of a shared [`Model`] with its own grid and random number generator.
+pub struct Run {
+    pub model: Arc<Model>,
+    pub mj: MarkovJunior,
+    /// Told about every node and change of the run, kept apart from `mj` so that grid scans can
+    /// share it between threads.
+    pub observer: Box<dyn Observer>,
+}
+
+impl Run {
+    pub fn new(model: Arc<Model>, seed: Option<u64>) -> Self {
+        let mj = model.initial_state(seed);
+        Run {
+            model,
+            mj,
+            observer: Box::new(()),
+        }
+    }
+
+    pub fn with_observer(mut self, observer: impl Observer + 'static) -> Self {
+        self.observer = Box::new(observer);
+        self
+    }
+
+    /// Runs the whole model, returning whether anything changed.
+    pub fn run(&mut self) -> bool {
+        self.mj
+            .apply_sequence_observed(&self.model.root, true, self.observer.as_mut())
+    }
+}
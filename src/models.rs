@@ -1,3 +1,9 @@
+use crate::*;
+use std::path::Path;
+
+/// Directory searched by [`find_model_xml`] before falling back to the built-in models.
+pub const MODELS_DIR: &str = "models";
+
 #[derive(Debug)]
 pub enum ModelKind {
     River,
@@ -18,113 +24,44 @@ impl std::str::FromStr for ModelKind {
     }
 }
 
-pub fn model_xml(model: ModelKind, size: usize) -> String {
+pub fn model_xml(model: ModelKind) -> &'static str {
     match model {
-        ModelKind::River => river_xml(size),
-        ModelKind::FireNoise => fire_noise_xml(size),
-        ModelKind::Test => test_xml(size),
+        ModelKind::River => include_str!("../models/River.xml"),
+        ModelKind::FireNoise => include_str!("../models/FireNoise.xml"),
+        ModelKind::Test => include_str!("../models/Test.xml"),
     }
 }
 
-// River https://github.com/mxgmn/MarkovJunior/blob/main/models/River.xml
-fn river_xml(size: usize) -> String {
-    format!(
-        r#"
-        <sequence fill="B" width="{size}" height="{size}">
-          <one in="B" out="W" steps="1"/>
-          <one in="B" out="R" steps="1"/>
-          <one>
-            <rule in="RB" out="RR"/>
-            <rule in="WB" out="WW"/>
-            </one>
-          <all in="RW" out="UU"/>
-          <all>
-            <rule in="W" out="B"/>
-            <rule in="R" out="B"/>
-            </all>
-          <all in="UB" out="UU" steps="1"/>
-          <all in="BU/UB" out="U*/**"/>
-          <all in="UB" out="*G"/>
-          <one in="B" out="E" steps="13"/>
-          <one>
-            <rule in="EB" out="*E"/>
-            <rule in="GB" out="*G"/>
-          </one>
-        </sequence>
-        "#
-    )
+pub fn read_model_file(path: impl AsRef<Path>) -> Result<String, ModelError> {
+    let path = path.as_ref();
+    std::fs::read_to_string(path).map_err(|err| ModelError::io(path, err))
+}
+
+/// Looks up `<models_dir>/<name>.xml`, falling back to the built-in model of the same name.
+pub fn find_model_xml(name: &str, models_dir: impl AsRef<Path>) -> Result<String, ModelError> {
+    let path = models_dir.as_ref().join(format!("{name}.xml"));
+    if path.is_file() {
+        return read_model_file(path);
+    }
+
+    name.parse::<ModelKind>()
+        .map(|model| model_xml(model).to_string())
+        .map_err(|_| ModelError::UnknownModel {
+            name: name.to_string(),
+        })
 }
 
-// FireNoise https://github.com/mxgmn/MarkovJunior/blob/main/models/FireNoise.xml
-fn fire_noise_xml(size: usize) -> String {
-    format!(
-        r#"
-        <sequence fill="B" width="{size}" height="{size}">
-          <prl steps="75">
-            <rule in="OG" out="*O"/>
-            <rule in="O*/*G" out="**/*O"/>
-            <rule in="B" out="G" p="0.01"/>
-            <rule in="O" out="B"/>
-            <rule in="G" out="O" p="0.0001"/>
-          </prl>
-          <all in="*G*/GBG" out="***/*G*"/>
-          <all>
-            <rule in="*B*/BGB/*B*" out="***/*B*/***"/>
-            <rule in="*BB*/BGGB/*BB*" out="****/*BB*/****"/>
-          </all>
-          <sequence>
-            <one in="G" out="R" steps="1"/>
-            <all in="RG" out="RR" steps="10"/>
-            <all in="RG" out="EE"/>
-            <all>
-              <rule in="ER" out="*E"/>
-              <rule in="EG" out="*E"/>
-            </all>
-          </sequence>
-          <sequence>
-            <one in="B" out="K" steps="1"/>
-            <all in="KB" out="*K" steps="10"/>
-            <all in="KB" out="GG"/>
-            <all>
-              <rule in="GB" out="*G"/>
-              <rule in="GK" out="*G"/>
-            </all>
-          </sequence>
-          <prl>
-            <rule in="K" out="E"/>
-            <rule in="G" out="B"/>
-          </prl>
-        </sequence>
-        "#
-    )
+pub fn load_model_file(
+    path: impl AsRef<Path>,
+    options: &ModelOptions,
+) -> Result<Model, ModelError> {
+    parse_model(&read_model_file(path)?, options)
 }
 
-fn test_xml(size: usize) -> String {
-    format!(
-        r#"
-        <sequence fill="B" width="{size}" height="{size}">
-          <prl steps="75">
-            <rule in="OG" out="*O"/>
-            <rule in="O*/*G" out="**/*O"/>
-            <rule in="B" out="G" p="0.01"/>
-            <rule in="O" out="B"/>
-            <rule in="G" out="O" p="0.0001"/>
-          </prl>
-          <all in="*G*/GBG" out="***/*G*"/>
-          <all>
-            <rule in="*B*/BGB/*B*" out="***/*B*/***"/>
-            <rule in="*BB*/BGGB/*BB*" out="****/*BB*/****"/>
-          </all>
-          <sequence>
-            <one in="G" out="R" steps="1"/>
-            <all in="RG" out="RR" steps="10"/>
-            <all in="RG" out="EE"/>
-            <all>
-              <rule in="ER" out="*E"/>
-              <rule in="EG" out="*E"/>
-            </all>
-          </sequence>
-        </sequence>
-        "#
-    )
+pub fn load_model(
+    name: &str,
+    models_dir: impl AsRef<Path>,
+    options: &ModelOptions,
+) -> Result<Model, ModelError> {
+    parse_model(&find_model_xml(name, models_dir)?, options)
 }
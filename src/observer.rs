@@ -0,0 +1,224 @@
+use crate::*;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+/// Callbacks for following a run, e.g. to render or log the grid as it changes.
+///
+/// Nodes are identified by their path of child indices from the root, see [`Sequence::node`].
+/// Every callback does nothing by default. Observers are `Send` so that runs can move between
+/// threads.
+pub trait Observer: Send {
+    /// A node is about to run. Rules inside a markov node start again on every activation.
+    fn on_node_start(&mut self, _mj: &MarkovJunior, _node: &RuleOrSequence, _path: &[usize]) {}
+
+    /// A node is done, after making `changes` pattern applications.
+    fn on_node_end(
+        &mut self,
+        _mj: &MarkovJunior,
+        _node: &RuleOrSequence,
+        _path: &[usize],
+        _changes: usize,
+    ) {
+    }
+
+    /// A rule step changed the symbols of these `(x, y, z)` cells.
+    fn on_cells_changed(&mut self, _mj: &MarkovJunior, _cells: &[(usize, usize, usize)]) {}
+
+    /// The root is done.
+    fn on_finish(&mut self, _mj: &MarkovJunior) {}
+}
+
+/// Observes nothing.
+impl Observer for () {}
+
+/// Forwards every callback to each observer in turn.
+impl Observer for Vec<Box<dyn Observer>> {
+    fn on_node_start(&mut self, mj: &MarkovJunior, node: &RuleOrSequence, path: &[usize]) {
+        for observer in self.iter_mut() {
+            observer.on_node_start(mj, node, path);
+        }
+    }
+
+    fn on_node_end(
+        &mut self,
+        mj: &MarkovJunior,
+        node: &RuleOrSequence,
+        path: &[usize],
+        changes: usize,
+    ) {
+        for observer in self.iter_mut() {
+            observer.on_node_end(mj, node, path, changes);
+        }
+    }
+
+    fn on_cells_changed(&mut self, mj: &MarkovJunior, cells: &[(usize, usize, usize)]) {
+        for observer in self.iter_mut() {
+            observer.on_cells_changed(mj, cells);
+        }
+    }
+
+    fn on_finish(&mut self, mj: &MarkovJunior) {
+        for observer in self.iter_mut() {
+            observer.on_finish(mj);
+        }
+    }
+}
+
+/// Whether the grid is worth logging after `node`: once for every child of the root, and after
+/// every sequence or markov node.
+fn is_log_point(node: &RuleOrSequence, path: &[usize]) -> bool {
+    path.len() == 1 || !matches!(node, RuleOrSequence::Rule(_))
+}
+
+/// Prints every child of the root once it is done, together with the changes made by each of
+/// its descendants, as a tree indented by depth.
+pub struct PrintObserver {
+    changes: BTreeMap<Vec<usize>, usize>,
+    out: Box<dyn Write + Send>,
+}
+
+impl Default for PrintObserver {
+    /// Prints to stdout.
+    fn default() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl PrintObserver {
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        PrintObserver {
+            changes: BTreeMap::new(),
+            out: Box::new(out),
+        }
+    }
+
+    fn print_node(&mut self, node: &RuleOrSequence, path: &mut Vec<usize>) -> io::Result<()> {
+        let indent = "  ".repeat(path.len() - 1);
+        let changes = self.changes.get(path).copied().unwrap_or_default();
+
+        let (label, sequence) = match node {
+            RuleOrSequence::Rule(rule) => return self.print_rule(rule, changes, &indent),
+            RuleOrSequence::Sequence(sequence) => ("Sequence", sequence),
+            RuleOrSequence::Markov(markov) => ("Markov", markov),
+        };
+
+        writeln!(
+            self.out,
+            "{indent}{label} steps: {:?}, changes: {changes}",
+            sequence.steps
+        )?;
+        for (index, child) in sequence.vec.iter().enumerate() {
+            path.push(index);
+            self.print_node(child, path)?;
+            path.pop();
+        }
+        Ok(())
+    }
+
+    fn print_rule(&mut self, rule: &Rule, changes: usize, indent: &str) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "{indent}Rule kind: {:?}, steps: {:?}, changes: {}",
+            rule.kind, rule.steps, changes
+        )?;
+        for pattern_rule in rule.patterns.iter() {
+            writeln!(
+                self.out,
+                "{indent}{} => {}",
+                pattern_rule.input.line, pattern_rule.output.line,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Observer for PrintObserver {
+    fn on_node_start(&mut self, _mj: &MarkovJunior, _node: &RuleOrSequence, path: &[usize]) {
+        // children of the root report the changes of their current run only
+        if path.len() == 1 {
+            self.changes
+                .retain(|node_path, _| !node_path.starts_with(path));
+        }
+    }
+
+    fn on_node_end(
+        &mut self,
+        _mj: &MarkovJunior,
+        node: &RuleOrSequence,
+        path: &[usize],
+        changes: usize,
+    ) {
+        *self.changes.entry(path.to_vec()).or_default() += changes;
+
+        if path.len() == 1 {
+            self.print_node(node, &mut path.to_vec())
+                .expect("Failed to write the report");
+        }
+    }
+}
+
+/// Rewrites a file with the grid after every child of the root and every nested sequence.
+pub struct OutputFileObserver {
+    pub path: String,
+}
+
+impl OutputFileObserver {
+    pub fn new(path: impl Into<String>) -> Self {
+        OutputFileObserver { path: path.into() }
+    }
+}
+
+impl Observer for OutputFileObserver {
+    fn on_node_end(
+        &mut self,
+        mj: &MarkovJunior,
+        node: &RuleOrSequence,
+        path: &[usize],
+        _changes: usize,
+    ) {
+        if !is_log_point(node, path) {
+            return;
+        }
+
+        let mut file = std::fs::File::create(&self.path).expect("Failed to open file");
+        mj.write_grid(&mut file).expect("Failed to write to file");
+    }
+}
+
+/// Runs a shell command at the same points as [`OutputFileObserver`] writes its file, e.g. to
+/// display that file.
+pub struct CommandObserver {
+    pub command: String,
+}
+
+impl CommandObserver {
+    pub fn new(command: impl Into<String>) -> Self {
+        CommandObserver {
+            command: command.into(),
+        }
+    }
+}
+
+impl Observer for CommandObserver {
+    fn on_node_end(
+        &mut self,
+        _mj: &MarkovJunior,
+        node: &RuleOrSequence,
+        path: &[usize],
+        _changes: usize,
+    ) {
+        if !is_log_point(node, path) {
+            return;
+        }
+
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdout(std::process::Stdio::inherit())
+            .stderr(std::process::Stdio::inherit())
+            .status()
+            .expect("Failed to execute shell command");
+    }
+}
@@ -1,19 +1,21 @@
-use std::cmp::Ordering;
-// use crate::*;
+use crate::*;
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug)]
 pub struct PatternRule {
     pub input: Pattern,
     pub output: Pattern,
     pub probability: Option<f32>,
-    pub canonical_key: Option<(usize, usize)>,
+    /// Key the input is matched through on flat grids, the one of the input unless it is cleared.
+    /// Patterns without one are matched cell by cell.
+    pub canonical_key: Option<CanonicalKey>,
 }
 
 pub const DEFAULT_PROBABILITY: f32 = 1.0;
 
 impl PatternRule {
     pub fn new(input: Pattern, output: Pattern, probability: Option<f32>) -> PatternRule {
-        let canonical_key = Self::calculate_canonical_key(input.width, input.height);
+        let canonical_key = input.canonical_key.clone();
 
         PatternRule {
             input,
@@ -23,69 +25,446 @@ impl PatternRule {
         }
     }
 
-    pub fn calculate_canonical_key(width: usize, height: usize) -> Option<(usize, usize)> {
-        if (width == 1 && height == 1) || (width != height) {
-            None
-        } else {
-            Some((width, height))
+    /// Restricts which rotations/reflections of the input are matched.
+    pub fn with_symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.input = self.input.with_symmetry(symmetry);
+        self
+    }
+
+    /// The rule for 3D grids: a copy of it for every tilt `symmetry` allows, each matching the
+    /// square transforms allowed after its tilt that no earlier copy matches already.
+    pub fn with_cube_symmetry(self, symmetry: Symmetry) -> Vec<PatternRule> {
+        let mut rules: Vec<PatternRule> = Vec::new();
+
+        for tilt in Tilt::ALL {
+            let input = self.input.tilted(tilt);
+            let transforms = Pattern::compute_rotations(&input.cells, input.width, input.height)
+                .into_iter()
+                .filter(|rotation| {
+                    symmetry.allows_tilted(tilt, rotation.rotation)
+                        && !rules.iter().any(|rule| {
+                            rule.input.cell_rotations.iter().any(|r| {
+                                r.data == rotation.data
+                                    && r.width == rotation.width
+                                    && r.height == rotation.height
+                            })
+                        })
+                })
+                .map(|rotation| rotation.rotation)
+                .collect::<Vec<_>>();
+            if transforms.is_empty() {
+                continue;
+            }
+
+            let output = self.output.tilted(tilt);
+            rules.push(
+                PatternRule::new(input, output, self.probability)
+                    .with_symmetry(Symmetry::from_transforms(&transforms)),
+            );
         }
+        rules
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
-pub struct RotatedSeq {
-    pub data: Vec<char>,
+pub struct RotatedSeq<T = char> {
+    pub data: Vec<T>,
     pub width: usize,
     pub height: usize,
-    pub rotation: isize, // 1, 2, 3, or 4 representing 0°, 90°, 180°, 270°, -1, -2, -3, or -4 representing mirrored 0°, 90°, 180°, 270°
+    pub depth: usize,
+    pub rotation: Transform,
 }
 
-#[derive(Debug)]
+#[derive(PartialEq, Debug)]
 pub struct PatternMatch {
     pub x: usize,
     pub y: usize,
+    pub z: usize,
     pub probability: Option<f32>,
     pub pattern_index: usize,
-    pub rotation: isize,
+    pub rotation: Transform,
 }
 
 pub const PATTERN_DELIMITER: char = '/';
+/// Separates the layers of 3D patterns, from the top one (highest z) down.
+pub const LAYER_DELIMITER: char = ' ';
 pub const ANYTHING: char = '*';
 pub const NOTHING: char = '❌';
+/// Usual symbol of input cells that lie outside the grid, which the root `border` attribute has
+/// to declare.
+pub const BORDER: char = '#';
+pub const CLASS_START: char = '[';
+pub const CLASS_END: char = ']';
+pub const CLASS_NEGATION: char = '^';
+pub const NEGATION: char = '!';
+
+/// Whether `symbol` has a meaning of its own in patterns, so that it can't be one of the `values`
+/// or stand for a union or the border.
+pub fn is_reserved(symbol: char) -> bool {
+    matches!(
+        symbol,
+        ANYTHING
+            | PATTERN_DELIMITER
+            | LAYER_DELIMITER
+            | CLASS_START
+            | CLASS_END
+            | CLASS_NEGATION
+            | NEGATION
+            | NOTHING
+    )
+}
+
+/// Set of grid symbols a single pattern cell accepts.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PatternCell {
+    Anything,
+    /// Outside of the grid, which lets windows hang off its edges.
+    Border,
+    Symbol(char),
+    OneOf(Vec<char>),
+    NoneOf(Vec<char>),
+}
+
+impl PatternCell {
+    pub fn literal(symbol: char) -> Self {
+        if symbol == ANYTHING {
+            PatternCell::Anything
+        } else {
+            PatternCell::Symbol(symbol)
+        }
+    }
+
+    pub fn one_of(mut symbols: Vec<char>) -> Self {
+        symbols.sort_unstable();
+        symbols.dedup();
+        match symbols[..] {
+            [symbol] => PatternCell::Symbol(symbol),
+            _ => PatternCell::OneOf(symbols),
+        }
+    }
+
+    pub fn none_of(mut symbols: Vec<char>) -> Self {
+        symbols.sort_unstable();
+        symbols.dedup();
+        PatternCell::NoneOf(symbols)
+    }
+
+    pub fn matches(&self, symbol: char) -> bool {
+        // `NOTHING` stands for the cells outside of the grid
+        match self {
+            PatternCell::Anything => symbol != NOTHING,
+            PatternCell::Border => symbol == NOTHING,
+            PatternCell::Symbol(s) => *s == symbol,
+            PatternCell::OneOf(symbols) => symbols.contains(&symbol),
+            PatternCell::NoneOf(symbols) => symbol != NOTHING && !symbols.contains(&symbol),
+        }
+    }
+
+    /// Symbols the cell refers to, whether it accepts or rejects them.
+    pub fn symbols(&self) -> &[char] {
+        match self {
+            PatternCell::Anything | PatternCell::Border => &[],
+            PatternCell::Symbol(symbol) => std::slice::from_ref(symbol),
+            PatternCell::OneOf(symbols) | PatternCell::NoneOf(symbols) => symbols,
+        }
+    }
+
+    /// Symbol stored for this cell in `Pattern::data`.
+    fn data_char(&self) -> char {
+        match self {
+            PatternCell::Symbol(symbol) => *symbol,
+            PatternCell::Border => NOTHING,
+            _ => ANYTHING,
+        }
+    }
+}
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Pattern {
     pub line: String,
+    /// Symbol of every cell, `NOTHING` for border cells, or `ANYTHING` for cells that accept more
+    /// than one symbol.
     pub data: Vec<char>,
+    pub cells: Vec<PatternCell>,
     pub width: usize,
     pub height: usize,
-    pub rotations: Vec<RotatedSeq>,
+    /// Number of layers, 1 for flat patterns.
+    pub depth: usize,
+    /// Every transform of the pattern, indexed by [`Transform::index`].
+    pub rotations: [RotatedSeq; 8],
     pub unique_rotations: Vec<RotatedSeq>,
+    /// Cells of the same rotations as `unique_rotations`, used for matching.
+    pub cell_rotations: Vec<RotatedSeq<PatternCell>>,
+    /// Smallest of the rotations that are at least as wide as they are tall, for patterns with a
+    /// [`CanonicalKey`].
     pub canonical_form: Option<RotatedSeq>,
+    pub canonical_id: Option<CanonicalId>,
+    pub canonical_key: Option<CanonicalKey>,
+    /// Same rotations as `cell_rotations`, in the same order, for patterns whose canonical key
+    /// [`CanonicalKey::is_packed`].
+    pub packed_rotations: Vec<PackedRotation>,
     pub has_wildcards: bool,
+    /// Whether some cells have to lie outside of the grid, in which case windows can hang off it.
+    pub has_border: bool,
+    pub symmetry: Symmetry,
 }
 
 impl Pattern {
+    /// Pattern where every character is a literal symbol, apart from `ANYTHING`.
     pub fn new(line: &str) -> Self {
-        let parts: Vec<&str> = line.split(PATTERN_DELIMITER).collect();
-        let width = parts[0].len();
-        let height = parts.len();
-        let data = parts.join("").chars().collect::<Vec<char>>();
+        Self::parse(line, |row| {
+            Ok(row.chars().map(PatternCell::literal).collect())
+        })
+        .expect("pattern rows should be non-empty and of the same width")
+    }
+
+    /// Parses a pattern that can use `[..]`/`[^..]` character classes, `!` negation and the
+    /// symbols of `unions`.
+    pub fn new_input(line: &str, unions: &BTreeMap<char, Vec<char>>) -> Result<Self, String> {
+        Self::parse(line, |row| Self::parse_input_row(row, unions, None))
+    }
+
+    /// Same as [`Pattern::new_input`] with `border` marking the cells outside of the grid.
+    pub fn new_input_with_border(
+        line: &str,
+        unions: &BTreeMap<char, Vec<char>>,
+        border: char,
+    ) -> Result<Self, String> {
+        let pattern = Self::parse(line, |row| Self::parse_input_row(row, unions, Some(border)))?;
+
+        if pattern
+            .cells
+            .iter()
+            .all(|cell| *cell == PatternCell::Border)
+        {
+            return Err(format!(
+                "'{border}' is outside the grid, so a pattern needs at least one other cell"
+            ));
+        }
+        Ok(pattern)
+    }
+
+    pub fn new_output(line: &str) -> Result<Self, String> {
+        Self::parse(line, |row| {
+            row.chars()
+                .map(|c| match c {
+                    CLASS_START | CLASS_END | NEGATION => Err(format!(
+                        "'{c}': character classes and negation can only be used in `in`"
+                    )),
+                    _ => Ok(PatternCell::literal(c)),
+                })
+                .collect()
+        })
+    }
+
+    fn parse(
+        line: &str,
+        parse_row: impl Fn(&str) -> Result<Vec<PatternCell>, String>,
+    ) -> Result<Self, String> {
+        let mut layers = line
+            .split(LAYER_DELIMITER)
+            .map(|layer| {
+                layer
+                    .split(PATTERN_DELIMITER)
+                    .map(&parse_row)
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let width = layers[0][0].len();
+        let height = layers[0].len();
+        let depth = layers.len();
+
+        if let Some(index) = layers.iter().position(|rows| rows.len() != height) {
+            return Err(format!(
+                "layer {} has {} rows but the first layer has {height}",
+                index + 1,
+                layers[index].len()
+            ));
+        }
+        layers.reverse();
+        let rows = layers.concat();
+        if width == 0 {
+            return Err("pattern rows must not be empty".to_string());
+        }
+        if let Some(index) = rows.iter().position(|row| row.len() != width) {
+            return Err(format!(
+                "row {} has {} cells but the first row has {width}",
+                index + 1,
+                rows[index].len()
+            ));
+        }
+
+        Ok(Self::from_cells(line, rows.concat(), width, height, depth))
+    }
+
+    fn parse_input_row(
+        row: &str,
+        unions: &BTreeMap<char, Vec<char>>,
+        border: Option<char>,
+    ) -> Result<Vec<PatternCell>, String> {
+        let expand = |symbol: char| match unions.get(&symbol) {
+            Some(values) => values.clone(),
+            None => vec![symbol],
+        };
+
+        let mut cells = Vec::new();
+        let mut chars = row.chars();
+
+        while let Some(c) = chars.next() {
+            let cell = match c {
+                ANYTHING => PatternCell::Anything,
+                _ if Some(c) == border => PatternCell::Border,
+                CLASS_START => {
+                    let mut negated = false;
+                    let mut symbols = Vec::new();
+                    loop {
+                        match chars.next() {
+                            None => return Err(format!("unclosed '{CLASS_START}'")),
+                            Some(CLASS_END) => break,
+                            Some(CLASS_NEGATION) if symbols.is_empty() && !negated => {
+                                negated = true
+                            }
+                            Some(c @ (CLASS_START | NEGATION | ANYTHING)) => {
+                                return Err(format!(
+                                    "'{c}' is not allowed inside a character class"
+                                ))
+                            }
+                            Some(c) if Some(c) == border => {
+                                return Err(format!(
+                                    "'{c}' is not allowed inside a character class"
+                                ))
+                            }
+                            Some(c) => symbols.extend(expand(c)),
+                        }
+                    }
+                    if symbols.is_empty() {
+                        return Err("empty character class".to_string());
+                    }
+                    if negated {
+                        PatternCell::none_of(symbols)
+                    } else {
+                        PatternCell::one_of(symbols)
+                    }
+                }
+                NEGATION => match chars.next() {
+                    None | Some(ANYTHING | CLASS_START | CLASS_END | NEGATION) => {
+                        return Err(format!("'{NEGATION}' must be followed by a symbol"))
+                    }
+                    Some(c) if Some(c) == border => {
+                        return Err(format!("'{NEGATION}' must be followed by a symbol"))
+                    }
+                    Some(c) => PatternCell::none_of(expand(c)),
+                },
+                CLASS_END => return Err(format!("unmatched '{CLASS_END}'")),
+                _ if unions.contains_key(&c) => PatternCell::one_of(expand(c)),
+                _ => PatternCell::Symbol(c),
+            };
+            cells.push(cell);
+        }
+
+        Ok(cells)
+    }
 
-        let has_wildcards = data.iter().any(|&char| char == ANYTHING);
-        let (maybe_canonical_form, rotations, unique_rotations) =
+    fn from_cells(
+        line: &str,
+        cells: Vec<PatternCell>,
+        width: usize,
+        height: usize,
+        depth: usize,
+    ) -> Self {
+        let data = cells.iter().map(PatternCell::data_char).collect::<Vec<_>>();
+        let has_wildcards = cells
+            .iter()
+            .any(|cell| !matches!(cell, PatternCell::Symbol(_) | PatternCell::Border));
+        let has_border = cells.contains(&PatternCell::Border);
+        let (maybe_canonical_form, rotations, _) =
             Self::compute_canonical_form_and_rotations(&data, width, height, has_wildcards);
+        // canonical forms are only kept for flat grids
+        let canonical_key = match depth {
+            1 => CanonicalKey::of(&cells, width, height),
+            _ => None,
+        };
 
         Pattern {
             line: line.to_string(),
             data,
+            cells,
             width,
             height,
+            depth,
             rotations,
-            unique_rotations,
+            unique_rotations: Vec::new(),
+            cell_rotations: Vec::new(),
+            canonical_id: maybe_canonical_form.as_ref().map(CanonicalId::of),
             canonical_form: maybe_canonical_form,
+            canonical_key,
+            packed_rotations: Vec::new(),
             has_wildcards,
+            has_border,
+            symmetry: Symmetry::ALL,
         }
+        .with_symmetry(Symmetry::ALL)
+    }
+
+    pub fn with_symmetry(mut self, symmetry: Symmetry) -> Self {
+        let cell_rotations = Self::compute_unique_rotations(
+            &Self::compute_rotations(&self.cells, self.width, self.height),
+            symmetry,
+        );
+
+        self.symmetry = symmetry;
+        self.unique_rotations = self
+            .rotations
+            .iter()
+            .filter(|r| cell_rotations.iter().any(|c| c.rotation == r.rotation))
+            .cloned()
+            .collect();
+        self.packed_rotations = match &self.canonical_key {
+            Some(canonical_key) if canonical_key.is_packed() => cell_rotations
+                .iter()
+                .map(|rotated_seq| PackedRotation::new(rotated_seq, canonical_key))
+                .collect(),
+            _ => Vec::new(),
+        };
+        self.cell_rotations = cell_rotations;
+        self
+    }
+
+    /// The pattern turned by `tilt`, with the same symmetry.
+    pub fn tilted(&self, tilt: Tilt) -> Self {
+        let (cells, [width, height, depth]) =
+            tilt.apply_to(&self.cells, [self.width, self.height, self.depth]);
+        Self::from_cells(&self.line, cells, width, height, depth).with_symmetry(self.symmetry)
+    }
+
+    /// The pattern transformed by `rotation`.
+    pub fn rotated(&self, rotation: Transform) -> &RotatedSeq {
+        &self.rotations[rotation.index()]
+    }
+
+    /// First rotation allowed by the symmetry that produces the same cells as `rotation`, which is
+    /// the one `MarkovJunior::pattern_fits` finds where `rotation` fits.
+    pub fn allowed_rotation(&self, rotation: Transform) -> Option<Transform> {
+        self.first_allowed_rotation(&[rotation])
+    }
+
+    /// Like [`Pattern::allowed_rotation`], for the first of several rotations that fit.
+    pub fn first_allowed_rotation(&self, rotations: &[Transform]) -> Option<Transform> {
+        let rotated_seqs = rotations
+            .iter()
+            .map(|&rotation| self.rotated(rotation))
+            .collect::<Vec<_>>();
+
+        self.unique_rotations
+            .iter()
+            .find(|r| {
+                rotated_seqs.iter().any(|rotated_seq| {
+                    r.data == rotated_seq.data
+                        && r.width == rotated_seq.width
+                        && r.height == rotated_seq.height
+                })
+            })
+            .map(|r| r.rotation)
     }
 
     pub fn compute_canonical_form_and_rotations(
@@ -93,99 +472,17 @@ impl Pattern {
         width: usize,
         height: usize,
         has_wildcards: bool,
-    ) -> (Option<RotatedSeq>, Vec<RotatedSeq>, Vec<RotatedSeq>) {
-        if width == 1 && height == 1 {
-            let rotation = RotatedSeq {
-                data: data.to_vec(),
-                width: 1,
-                height: 1,
-                rotation: 1,
-            };
+    ) -> (Option<RotatedSeq>, [RotatedSeq; 8], Vec<RotatedSeq>) {
+        let rotations = Self::compute_rotations(data, width, height);
+        let unique_rotations = Self::compute_unique_rotations(&rotations, Symmetry::ALL);
 
-            return (None, [rotation.clone()].to_vec(), [rotation].to_vec());
-        }
-
-        let rotations = vec![
-            RotatedSeq {
-                data: data.to_vec(),
-                width,
-                height,
-                rotation: 1,
-            },
-            RotatedSeq {
-                data: Self::rotate_90(data, width, height),
-                width: height,
-                height: width,
-                rotation: 2,
-            },
-            RotatedSeq {
-                data: Self::rotate_180(data),
-                width,
-                height,
-                rotation: 3,
-            },
-            RotatedSeq {
-                data: Self::rotate_270(data, width, height),
-                width: height,
-                height: width,
-                rotation: 4,
-            },
-            RotatedSeq {
-                data: Self::mirror(data, width),
-                width,
-                height,
-                rotation: -1,
-            },
-            RotatedSeq {
-                data: Self::rotate_90(&Self::mirror(data, width), width, height),
-                width: height,
-                height: width,
-                rotation: -2,
-            },
-            RotatedSeq {
-                data: Self::rotate_180(&Self::mirror(data, width)),
-                width,
-                height,
-                rotation: -3,
-            },
-            RotatedSeq {
-                data: Self::rotate_270(&Self::mirror(data, width), width, height),
-                width: height,
-                height: width,
-                rotation: -4,
-            },
-        ];
-
-        let mut unique_rotations = Vec::new();
-        for rotation in rotations.iter() {
-            if !unique_rotations.iter().any(|r: &RotatedSeq| {
-                r.data == rotation.data && r.width == rotation.width && r.height == rotation.height
-            }) {
-                unique_rotations.push(rotation.clone());
-            }
-        }
-
-        if has_wildcards || width != height {
+        if (width == 1 && height == 1) || has_wildcards {
             (None, rotations, unique_rotations)
         } else {
             let canonical_form = rotations
                 .iter()
-                .min_by(|a, b| {
-                    let data_cmp = a
-                        .data
-                        .iter()
-                        .collect::<String>()
-                        .cmp(&b.data.iter().collect::<String>());
-                    if data_cmp == Ordering::Equal {
-                        match (a.rotation >= 0, b.rotation >= 0) {
-                            (true, false) => Ordering::Less,
-                            (false, true) => Ordering::Greater,
-                            _ => a.rotation.abs().cmp(&b.rotation.abs()),
-                        }
-                    } else {
-                        data_cmp
-                    }
-                })
+                .filter(|rotated_seq| rotated_seq.width >= rotated_seq.height)
+                .min_by(|a, b| a.data.cmp(&b.data).then(a.rotation.cmp(&b.rotation)))
                 .unwrap()
                 .clone();
 
@@ -193,134 +490,29 @@ impl Pattern {
         }
     }
 
-    pub fn mirror(data: &[char], width: usize) -> Vec<char> {
-        let mut mirrored = Vec::with_capacity(data.len());
-        for chunk in data.chunks(width) {
-            mirrored.extend(chunk.iter().rev());
-        }
-        mirrored
-    }
-
-    pub fn rotate_90(data: &[char], width: usize, height: usize) -> Vec<char> {
-        let mut rotated_data = vec![' '; width * height];
-        for y in 0..height {
-            for x in 0..width {
-                rotated_data[x * height + (height - 1 - y)] = data[y * width + x];
-            }
-        }
-        rotated_data
-    }
-
-    pub fn rotate_180(data: &[char]) -> Vec<char> {
-        let mut rotated_data = data.to_owned();
-        rotated_data.reverse();
-        rotated_data.to_vec()
-    }
-
-    pub fn rotate_270(data: &[char], width: usize, height: usize) -> Vec<char> {
-        let mut rotated_data = vec![' '; width * height];
-        for y in 0..height {
-            for x in 0..width {
-                rotated_data[(width - 1 - x) * height + y] = data[y * width + x];
-            }
-        }
-        rotated_data
-    }
-
-    pub fn rollback_rotation(
-        data: &[char],
+    pub fn compute_rotations<T: Clone>(
+        data: &[T],
         width: usize,
         height: usize,
-        rotation: isize,
-    ) -> Vec<char> {
-        match rotation {
-            -4 => Self::mirror(&Self::rotate_90(data, width, height), width),
-            -3 => Self::mirror(&Self::rotate_180(data), width),
-            -2 => Self::mirror(&Self::rotate_270(data, width, height), width),
-            -1 => Self::mirror(data, width),
-            1 => data.to_vec(),
-            2 => Self::rotate_270(data, width, height),
-            3 => Self::rotate_180(data),
-            4 => Self::rotate_90(data, width, height),
-            _ => unreachable!(),
-        }
+    ) -> [RotatedSeq<T>; 8] {
+        Transform::ALL.map(|transform| transform.apply_to(data, width, height))
     }
 
-    pub fn calculate_relative_rotation(grid_rotation: isize, pattern_rotation: isize) -> isize {
-        match (grid_rotation, pattern_rotation) {
-            (1, _) => pattern_rotation,
-            // (1, 1) => 1,
-            // (1, 2) => 2,
-            // (1, 3) => 3,
-            // (1, 4) => 4,
-            // (1, -1) => -1,
-            // (1, -2) => -2,
-            // (1, -3) => -3,
-            // (1, -4) => -4,
-            (2, 1) => 4,
-            (2, 2) => 1,
-            (2, 3) => 2,
-            (2, 4) => 3,
-            (2, -1) => -4,
-            (2, -2) => -1,
-            (2, -3) => -2,
-            (2, -4) => -3,
-
-            (3, 1) => 3,
-            (3, 2) => 4,
-            (3, 3) => 1,
-            (3, 4) => 2,
-            (3, -1) => -3,
-            (3, -2) => -4,
-            (3, -3) => -1,
-            (3, -4) => -2,
-
-            (4, 1) => 2,
-            (4, 2) => 3,
-            (4, 3) => 4,
-            (4, 4) => 1,
-            (4, -1) => -2,
-            (4, -2) => -3,
-            (4, -3) => -4,
-            (4, -4) => -1,
-
-            (-1, 1) => -1,
-            (-1, 2) => -4,
-            (-1, 3) => -3,
-            (-1, 4) => -2,
-            (-1, -1) => 1,
-            (-1, -2) => 4,
-            (-1, -3) => 3,
-            (-1, -4) => 2,
-
-            (-2, 1) => -2,
-            (-2, 2) => -1,
-            (-2, 3) => -4,
-            (-2, 4) => -3,
-            (-2, -1) => 2,
-            (-2, -2) => 1,
-            (-2, -3) => 4,
-            (-2, -4) => 3,
-
-            (-3, 1) => -3,
-            (-3, 2) => -2,
-            (-3, 3) => -1,
-            (-3, 4) => -4,
-            (-3, -1) => 3,
-            (-3, -2) => 2,
-            (-3, -3) => 1,
-            (-3, -4) => 4,
-
-            (-4, 1) => -4,
-            (-4, 2) => -3,
-            (-4, 3) => -2,
-            (-4, 4) => -1,
-            (-4, -1) => 4,
-            (-4, -2) => 3,
-            (-4, -3) => 2,
-            (-4, -4) => 1,
-
-            _ => unreachable!(),
+    pub fn compute_unique_rotations<T: Clone + PartialEq>(
+        rotations: &[RotatedSeq<T>],
+        symmetry: Symmetry,
+    ) -> Vec<RotatedSeq<T>> {
+        let mut unique_rotations = Vec::new();
+        for rotation in rotations.iter() {
+            if !symmetry.allows(rotation.rotation) {
+                continue;
+            }
+            if !unique_rotations.iter().any(|r: &RotatedSeq<T>| {
+                r.data == rotation.data && r.width == rotation.width && r.height == rotation.height
+            }) {
+                unique_rotations.push(rotation.clone());
+            }
         }
+        unique_rotations
     }
 }
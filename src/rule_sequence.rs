@@ -6,17 +6,62 @@ pub struct Sequence {
     pub steps: Option<usize>,
 }
 
+impl Sequence {
+    /// Node at the end of a path of child indices, or `None` for the empty path.
+    ///
+    /// Panics if the path leads through a rule or past the last child.
+    pub fn node(&self, path: &[usize]) -> Option<&RuleOrSequence> {
+        let (&first, rest) = path.split_first()?;
+
+        Some(
+            rest.iter()
+                .fold(&self.vec[first], |node, &child| match node {
+                    RuleOrSequence::Sequence(sequence) | RuleOrSequence::Markov(sequence) => {
+                        &sequence.vec[child]
+                    }
+                    RuleOrSequence::Rule(_) => panic!("rules have no children"),
+                }),
+        )
+    }
+}
+
 #[derive(Debug)]
 pub enum RuleOrSequence {
     Rule(Rule),
     Sequence(Sequence),
+    /// Restarts from its first child after every child that changes the grid.
+    Markov(Sequence),
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum RuleKind {
     One,
     All,
-    Parallel,
+    Parallel(ConflictPolicy),
+}
+
+/// How a `<prl>` rule resolves matches that write different symbols to the same cell, set with
+/// its `conflicts` attribute.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ConflictPolicy {
+    /// Matches are considered in random order and each one is applied unless it conflicts with
+    /// one applied before it.
+    #[default]
+    Random,
+    /// None of the conflicting matches are applied.
+    Skip,
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(ConflictPolicy::Random),
+            "skip" => Ok(ConflictPolicy::Skip),
+            _ => Err("expected random or skip".to_string()),
+        }
+    }
 }
 
 #[derive(Debug)]
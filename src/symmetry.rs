@@ -0,0 +1,82 @@
+use crate::*;
+use std::str::FromStr;
+
+/// Subgroup of the symmetries a rule is allowed to match with, stored as a bitmask over the
+/// square transforms made after each tilt, at `tilt.index() * 8 + transform.index()`. Flat
+/// grids only use the ones after [`Tilt::ZUp`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Symmetry(u64);
+
+impl Symmetry {
+    /// Every symmetry of the square, the default of flat grids.
+    pub const ALL: Symmetry = Symmetry(0xff);
+    /// Every symmetry of the cube, the default of 3D grids.
+    pub const CUBE: Symmetry = Symmetry((1 << 48) - 1);
+
+    pub fn from_transforms(transforms: &[Transform]) -> Self {
+        Self::from_tilted_transforms(transforms.iter().map(|&transform| (Tilt::ZUp, transform)))
+    }
+
+    pub fn from_tilted_transforms(transforms: impl IntoIterator<Item = (Tilt, Transform)>) -> Self {
+        Symmetry(transforms.into_iter().fold(0, |mask, (tilt, transform)| {
+            mask | 1 << (tilt.index() * 8 + transform.index())
+        }))
+    }
+
+    pub fn allows(&self, transform: Transform) -> bool {
+        self.allows_tilted(Tilt::ZUp, transform)
+    }
+
+    pub fn allows_tilted(&self, tilt: Tilt, transform: Transform) -> bool {
+        self.0 & 1 << (tilt.index() * 8 + transform.index()) != 0
+    }
+
+    /// Square transforms allowed after `tilt`.
+    pub fn after_tilt(&self, tilt: Tilt) -> Symmetry {
+        Symmetry(self.0 >> (tilt.index() * 8) & 0xff)
+    }
+}
+
+impl Default for Symmetry {
+    fn default() -> Self {
+        Symmetry::ALL
+    }
+}
+
+impl FromStr for Symmetry {
+    type Err = String;
+
+    // the notation follows https://github.com/mxgmn/MarkovJunior/blob/main/source/SymmetryHelper.cs
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Transform::*;
+
+        match s {
+            "()" => Ok(Symmetry::from_transforms(&[Identity])),
+            "(x)" => Ok(Symmetry::from_transforms(&[Identity, Mirror])),
+            "(y)" => Ok(Symmetry::from_transforms(&[Identity, MirrorRotate180])),
+            "(x)(y)" => Ok(Symmetry::from_transforms(&[
+                Identity,
+                Mirror,
+                Rotate180,
+                MirrorRotate180,
+            ])),
+            "(xy+)" => Ok(Symmetry::from_transforms(&[
+                Identity, Rotate90, Rotate180, Rotate270,
+            ])),
+            "(xy)" => Ok(Symmetry::ALL),
+            // turning the block upside down and back, row by row, mirrors it along z
+            "(z)" => Ok(Symmetry::from_tilted_transforms([
+                (Tilt::ZUp, Identity),
+                (Tilt::ZDown, MirrorRotate180),
+            ])),
+            // tilts are rotations, so the rotations are the ones without mirroring
+            "(xyz+)" => Ok(Symmetry::from_tilted_transforms(Tilt::ALL.iter().flat_map(
+                |&tilt| [Identity, Rotate90, Rotate180, Rotate270].map(|rotation| (tilt, rotation)),
+            ))),
+            "(xyz)" => Ok(Symmetry::CUBE),
+            _ => Err(
+                "expected one of (), (x), (y), (x)(y), (xy+), (xy), (z), (xyz+), (xyz)".to_string(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::*;
+
+/// One of the eight symmetries of the square: a clockwise rotation by a number of quarter turns,
+/// optionally preceded by mirroring left to right.
+///
+/// The order of the variants is the one [`Pattern::rotations`] and every other per-transform
+/// array are indexed by, which is also the order that breaks ties between equal rotations.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[repr(u8)]
+pub enum Transform {
+    #[default]
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Mirror,
+    MirrorRotate90,
+    MirrorRotate180,
+    MirrorRotate270,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::Mirror,
+        Transform::MirrorRotate90,
+        Transform::MirrorRotate180,
+        Transform::MirrorRotate270,
+    ];
+
+    fn from_parts(mirrored: bool, quarter_turns: u8) -> Self {
+        Self::ALL[mirrored as usize * 4 + quarter_turns as usize % 4]
+    }
+
+    /// Position in [`Transform::ALL`].
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn is_mirrored(self) -> bool {
+        self.index() >= 4
+    }
+
+    /// Clockwise quarter turns made after the mirroring, if any.
+    pub fn quarter_turns(self) -> u8 {
+        self as u8 % 4
+    }
+
+    /// Whether the width and the height trade places.
+    pub fn swaps_sides(self) -> bool {
+        self.quarter_turns() % 2 == 1
+    }
+
+    /// Transform doing `self` and then `next`.
+    pub fn then(self, next: Transform) -> Transform {
+        // mirroring turns the rotations made before it the other way around
+        let quarter_turns = if next.is_mirrored() {
+            next.quarter_turns() + 4 - self.quarter_turns()
+        } else {
+            next.quarter_turns() + self.quarter_turns()
+        };
+        Self::from_parts(self.is_mirrored() != next.is_mirrored(), quarter_turns)
+    }
+
+    /// Transform undoing `self`.
+    pub fn inverse(self) -> Transform {
+        if self.is_mirrored() {
+            self
+        } else {
+            Self::from_parts(false, 4 - self.quarter_turns())
+        }
+    }
+
+    /// Cell of a `width` by `height` block that `self` moves to `x`, `y`.
+    pub fn source(self, width: usize, height: usize, x: usize, y: usize) -> (usize, usize) {
+        let (last_x, last_y) = (width - 1, height - 1);
+        match self {
+            Transform::Identity => (x, y),
+            Transform::Rotate90 => (y, last_y - x),
+            Transform::Rotate180 => (last_x - x, last_y - y),
+            Transform::Rotate270 => (last_x - y, x),
+            Transform::Mirror => (last_x - x, y),
+            Transform::MirrorRotate90 => (last_x - y, last_y - x),
+            Transform::MirrorRotate180 => (x, last_y - y),
+            Transform::MirrorRotate270 => (y, x),
+        }
+    }
+
+    /// Transforms the `width` by `height` block `data`, stored row by row and, for blocks with
+    /// several layers, layer by layer. Every layer is transformed in place.
+    pub fn apply_to<T: Clone>(self, data: &[T], width: usize, height: usize) -> RotatedSeq<T> {
+        let (rotated_width, rotated_height) = if self.swaps_sides() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let layer = width * height;
+
+        RotatedSeq {
+            data: (0..data.len())
+                .map(|i| {
+                    let (x, y) =
+                        self.source(width, height, i % rotated_width, i % layer / rotated_width);
+                    data[i / layer * layer + y * width + x].clone()
+                })
+                .collect(),
+            width: rotated_width,
+            height: rotated_height,
+            depth: data.len() / layer,
+            rotation: self,
+        }
+    }
+}
+
+/// Turn of a block that decides which of its axes ends up along the z axis. Followed by one of
+/// the square [`Transform`]s, which keep the z axis in place, it makes up each of the 48
+/// symmetries of the cube.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+#[repr(u8)]
+pub enum Tilt {
+    #[default]
+    ZUp,
+    ZDown,
+    XUp,
+    XDown,
+    YUp,
+    YDown,
+}
+
+impl Tilt {
+    pub const ALL: [Tilt; 6] = [
+        Tilt::ZUp,
+        Tilt::ZDown,
+        Tilt::XUp,
+        Tilt::XDown,
+        Tilt::YUp,
+        Tilt::YDown,
+    ];
+
+    /// Position in [`Tilt::ALL`].
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Axis of the original block that each axis of the tilted one runs along, and whether it
+    /// runs the other way. Every tilt is a rotation.
+    fn axes(self) -> [(usize, bool); 3] {
+        match self {
+            Tilt::ZUp => [(0, false), (1, false), (2, false)],
+            Tilt::ZDown => [(0, false), (1, true), (2, true)],
+            Tilt::XUp => [(2, true), (1, false), (0, false)],
+            Tilt::XDown => [(2, false), (1, false), (0, true)],
+            Tilt::YUp => [(0, false), (2, true), (1, false)],
+            Tilt::YDown => [(0, false), (2, false), (1, true)],
+        }
+    }
+
+    /// Width, height and depth of a block of the given `size` once tilted.
+    pub fn size(self, size: [usize; 3]) -> [usize; 3] {
+        self.axes().map(|(axis, _)| size[axis])
+    }
+
+    /// Tilts the block `data` of the given `size`, stored row by row and layer by layer,
+    /// returning it together with its new size.
+    pub fn apply_to<T: Clone>(self, data: &[T], size: [usize; 3]) -> (Vec<T>, [usize; 3]) {
+        let axes = self.axes();
+        let tilted_size = self.size(size);
+
+        let tilted = (0..data.len())
+            .map(|i| {
+                let position = [
+                    i % tilted_size[0],
+                    i / tilted_size[0] % tilted_size[1],
+                    i / (tilted_size[0] * tilted_size[1]),
+                ];
+                let mut source = [0; 3];
+                for (axis, &(source_axis, reversed)) in axes.iter().enumerate() {
+                    source[source_axis] = if reversed {
+                        tilted_size[axis] - 1 - position[axis]
+                    } else {
+                        position[axis]
+                    };
+                }
+                data[(source[2] * size[1] + source[1]) * size[0] + source[0]].clone()
+            })
+            .collect();
+        (tilted, tilted_size)
+    }
+}
@@ -0,0 +1,49 @@
+use std::str::FromStr;
+
+/// Axes along which the grid wraps around, so that patterns reaching past one edge continue at
+/// the opposite one, e.g. for tileable textures.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct Wrap {
+    pub x: bool,
+    pub y: bool,
+    pub z: bool,
+}
+
+impl Wrap {
+    pub const NONE: Wrap = Wrap {
+        x: false,
+        y: false,
+        z: false,
+    };
+    pub const ALL: Wrap = Wrap {
+        x: true,
+        y: true,
+        z: true,
+    };
+}
+
+impl FromStr for Wrap {
+    type Err = String;
+
+    /// Reads the axes in any order, e.g. `x`, `xy` or `xyz`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut wrap = Wrap::NONE;
+        for axis in s.chars() {
+            let wraps = match axis {
+                'x' => &mut wrap.x,
+                'y' => &mut wrap.y,
+                'z' => &mut wrap.z,
+                _ => return Err("expected some of the axes x, y and z, e.g. xy".to_string()),
+            };
+            if *wraps {
+                return Err(format!("axis {axis} is repeated"));
+            }
+            *wraps = true;
+        }
+
+        if wrap == Wrap::NONE {
+            return Err("expected some of the axes x, y and z, e.g. xy".to_string());
+        }
+        Ok(wrap)
+    }
+}
@@ -1,74 +1,552 @@
 use crate::*;
 use roxmltree::Node;
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
 
-fn parse_rule_or_sequence(node: &Node) -> RuleOrSequence {
+/// Settings a node inherits from its ancestors.
+#[derive(Clone, Default)]
+struct Scope {
+    symmetry: Symmetry,
+    unions: BTreeMap<char, Vec<char>>,
+    /// Symbols declared by the root `values`; nothing is validated without them.
+    values: Option<Alphabet>,
+    /// Symbol of the input cells outside of the grid, if the root declares one.
+    border: Option<char>,
+    /// Number of layers of the grid, which rules turn through every tilt their symmetry allows
+    /// once there are several.
+    length: usize,
+}
+
+impl Scope {
+    fn enter(&self, node: &Node) -> Result<Scope, ModelError> {
+        let mut scope = self.clone();
+
+        if let Some(symmetry) = parse_attribute(node, "symmetry")? {
+            scope.symmetry = symmetry;
+        }
+        for union in node
+            .children()
+            .filter(|n| n.is_element() && n.tag_name().name() == "union")
+        {
+            let (symbol, values) = parse_union(&union, self)?;
+            scope.unions.insert(symbol, values);
+        }
+
+        Ok(scope)
+    }
+}
+
+fn parse_rule_or_sequence(node: &Node, scope: &Scope) -> Result<RuleOrSequence, ModelError> {
     match node.tag_name().name() {
-        "sequence" => RuleOrSequence::Sequence(parse_sequence(node, false)),
-        _ => RuleOrSequence::Rule(parse_rule(node)),
+        "sequence" => Ok(RuleOrSequence::Sequence(parse_sequence(
+            node, false, scope,
+        )?)),
+        "markov" => Ok(RuleOrSequence::Markov(parse_sequence(node, false, scope)?)),
+        _ => Ok(RuleOrSequence::Rule(parse_rule(node, scope)?)),
     }
 }
 
-fn parse_sequence(node: &Node, is_root: bool) -> Sequence {
-    let vec = node
-        .children()
-        .filter(|n| n.is_element())
-        .map(|n| parse_rule_or_sequence(&n))
-        .collect();
+fn parse_sequence(node: &Node, is_root: bool, scope: &Scope) -> Result<Sequence, ModelError> {
+    let scope = scope.enter(node)?;
+    let vec = child_nodes(node)
+        .map(|n| parse_rule_or_sequence(&n, &scope))
+        .collect::<Result<_, _>>()?;
 
-    let steps = node.attribute("steps").and_then(|s| s.parse().ok());
+    let steps = parse_attribute(node, "steps")?;
 
-    Sequence {
+    Ok(Sequence {
         vec,
         steps: if steps.is_none() && is_root {
             Some(1)
         } else {
             steps
         },
-    }
+    })
 }
 
-fn parse_rule(node: &Node) -> Rule {
+fn parse_rule(node: &Node, scope: &Scope) -> Result<Rule, ModelError> {
     let rule_kind = match node.tag_name().name() {
         "one" => RuleKind::One,
         "all" => RuleKind::All,
-        "prl" => RuleKind::Parallel,
-        _ => panic!("Unknown rule kind: {}", node.tag_name().name()),
+        "prl" => RuleKind::Parallel(parse_attribute(node, "conflicts")?.unwrap_or_default()),
+        _ => return Err(ModelError::unknown_node(node)),
     };
+    if node.has_attribute("conflicts") && !matches!(rule_kind, RuleKind::Parallel(_)) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "conflicts",
+            "only `<prl>` rules have conflicts to resolve",
+        ));
+    }
 
-    let steps = node.attribute("steps").and_then(|s| s.parse().ok());
+    let steps = parse_attribute(node, "steps")?;
+    let scope = scope.enter(node)?;
 
-    let patterns = if node.has_attribute("in") && node.has_attribute("out") {
-        vec![PatternRule::new(
-            Pattern::new(node.attribute("in").unwrap()),
-            Pattern::new(node.attribute("out").unwrap()),
-            node.attribute("p").and_then(|s| s.parse().ok()),
-        )]
+    let patterns = if node.has_attribute("in") || node.has_attribute("out") {
+        parse_pattern_rule(node, &scope)?
     } else {
-        node.children()
-            .filter(|n| n.is_element() && n.tag_name().name() == "rule")
-            .map(|n| {
-                PatternRule::new(
-                    Pattern::new(n.attribute("in").unwrap()),
-                    Pattern::new(n.attribute("out").unwrap()),
-                    n.attribute("p").and_then(|s| s.parse().ok()),
-                )
+        let rules = child_nodes(node)
+            .map(|n| match n.tag_name().name() {
+                "rule" => parse_pattern_rule(&n, &scope.enter(&n)?),
+                _ => Err(ModelError::unknown_node(&n)),
             })
-            .collect()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if rules.is_empty() {
+            return Err(ModelError::missing_attribute(node, "in"));
+        }
+        rules.concat()
     };
 
-    Rule::new(rule_kind, patterns, steps)
+    Ok(Rule::new(rule_kind, patterns, steps))
 }
 
-pub fn parse_xml(xml: &str, seed: Option<u64>) -> (MarkovJunior, Sequence) {
-    let doc = roxmltree::Document::parse(xml).unwrap();
-    let root = doc.root_element();
+/// Parses a rule with its symmetry, which takes a copy of it for every tilt on 3D grids.
+fn parse_pattern_rule(node: &Node, scope: &Scope) -> Result<Vec<PatternRule>, ModelError> {
+    let input = parse_pattern(node, "in", |line| match scope.border {
+        Some(border) => Pattern::new_input_with_border(line, &scope.unions, border),
+        None => Pattern::new_input(line, &scope.unions),
+    })?;
+    let output = parse_pattern(node, "out", Pattern::new_output)?;
+
+    if input.depth > 1 && scope.length == 1 {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "in",
+            format!(
+                "the pattern has {} layers but the grid has a single one, set the root `length`",
+                input.depth
+            ),
+        ));
+    }
+    if (input.width, input.height, input.depth) != (output.width, output.height, output.depth) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "out",
+            format!(
+                "output is {} but input is {}",
+                pattern_size(&output),
+                pattern_size(&input)
+            ),
+        ));
+    }
+    if let Some(symbol) = output.data.iter().find(|c| scope.unions.contains_key(c)) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "out",
+            format!("union symbol '{symbol}' can only be used in `in`"),
+        ));
+    }
+    if let Some(border) = scope.border {
+        if output.data.contains(&border) {
+            return Err(ModelError::invalid_attribute(
+                node,
+                "out",
+                format!("the border '{border}' can only be used in `in`"),
+            ));
+        }
+        // nothing can be written outside of the grid
+        if input
+            .cells
+            .iter()
+            .zip(&output.data)
+            .any(|(cell, &symbol)| *cell == PatternCell::Border && symbol != ANYTHING)
+        {
+            return Err(ModelError::invalid_attribute(
+                node,
+                "out",
+                format!("cells that are '{border}' in `in` must be '{ANYTHING}' in `out`"),
+            ));
+        }
+    }
+    if let Some(values) = &scope.values {
+        check_symbols(node, "in", pattern_symbols(&input), values)?;
+        check_symbols(node, "out", pattern_symbols(&output), values)?;
+    }
+
+    let probability = parse_attribute::<f32>(node, "p")?;
+    if probability.is_some_and(|p| !p.is_finite() || p < 0.0) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "p",
+            "must be a finite number that is not negative",
+        ));
+    }
+    let pattern_rule = PatternRule::new(input, output, probability);
+    if scope.length > 1 {
+        Ok(pattern_rule.with_cube_symmetry(scope.symmetry))
+    } else {
+        Ok(vec![pattern_rule.with_symmetry(scope.symmetry)])
+    }
+}
+
+/// Size of a pattern as `WxH`, or `WxHxD` once it has several layers.
+fn pattern_size(pattern: &Pattern) -> String {
+    match pattern.depth {
+        1 => format!("{}x{}", pattern.width, pattern.height),
+        depth => format!("{}x{}x{depth}", pattern.width, pattern.height),
+    }
+}
+
+fn parse_pattern(
+    node: &Node,
+    attribute: &str,
+    parse: impl FnOnce(&str) -> Result<Pattern, String>,
+) -> Result<Pattern, ModelError> {
+    let line = required_attribute(node, attribute)?;
+    parse(line).map_err(|reason| ModelError::invalid_attribute(node, attribute, reason))
+}
+
+fn parse_union(node: &Node, scope: &Scope) -> Result<(char, Vec<char>), ModelError> {
+    let maybe_values = scope.values.as_ref();
+    let symbol = parse_attribute::<char>(node, "symbol")?
+        .ok_or_else(|| ModelError::missing_attribute(node, "symbol"))?;
+    if is_reserved(symbol) || Some(symbol) == scope.border {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "symbol",
+            format!("'{symbol}' is reserved"),
+        ));
+    }
+    if maybe_values.is_some_and(|values| values.contains(symbol)) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "symbol",
+            format!("'{symbol}' is already one of the root `values`"),
+        ));
+    }
+
+    let values = required_attribute(node, "values")?
+        .chars()
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "values",
+            "a union needs at least one value",
+        ));
+    }
+    if let Some(&value) = values.iter().find(|&&value| is_reserved(value)) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "values",
+            format!("'{value}' is reserved"),
+        ));
+    }
+    if let Some(values_alphabet) = maybe_values {
+        check_symbols(node, "values", values.iter().copied(), values_alphabet)?;
+    }
+
+    Ok((symbol, values))
+}
+
+/// Every symbol a pattern refers to, apart from `ANYTHING`.
+fn pattern_symbols(pattern: &Pattern) -> impl Iterator<Item = char> + '_ {
+    pattern
+        .cells
+        .iter()
+        .flat_map(|cell| cell.symbols().iter().copied())
+}
+
+/// Symbols used by all patterns of `sequence`, in document order.
+fn sequence_symbols(sequence: &Sequence) -> Vec<char> {
+    sequence
+        .vec
+        .iter()
+        .flat_map(|node| match node {
+            RuleOrSequence::Rule(rule) => rule
+                .patterns
+                .iter()
+                .flat_map(|pattern_rule| {
+                    pattern_symbols(&pattern_rule.input)
+                        .chain(pattern_symbols(&pattern_rule.output))
+                })
+                .collect(),
+            RuleOrSequence::Sequence(sequence) | RuleOrSequence::Markov(sequence) => {
+                sequence_symbols(sequence)
+            }
+        })
+        .collect()
+}
+
+fn check_symbols(
+    node: &Node,
+    attribute: &str,
+    symbols: impl IntoIterator<Item = char>,
+    values: &Alphabet,
+) -> Result<(), ModelError> {
+    match symbols.into_iter().find(|&symbol| !values.contains(symbol)) {
+        Some(symbol) => Err(ModelError::unknown_symbol(node, attribute, symbol, values)),
+        None => Ok(()),
+    }
+}
+
+/// Child elements that are nodes, i.e. everything except scope declarations like `<union>` and
+/// the `<put>` placements of the root.
+fn child_nodes<'a, 'input>(node: &Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    let is_root = node.parent_element().is_none();
+
+    node.children().filter(move |n| {
+        n.is_element()
+            && match n.tag_name().name() {
+                "union" => false,
+                "put" => !is_root,
+                _ => true,
+            }
+    })
+}
+
+/// Initial placement of a symbol, declared as `<put x=".." y=".." value=".."/>` on the root,
+/// with a `z` as well on 3D grids.
+fn parse_put(
+    node: &Node,
+    [width, height, length]: [usize; 3],
+    maybe_values: Option<&Alphabet>,
+) -> Result<(usize, usize, usize, char), ModelError> {
+    let grid_size = match length {
+        1 => format!("{width}x{height}"),
+        _ => format!("{width}x{height}x{length}"),
+    };
+    let coordinate = |attribute, size, maybe_default| -> Result<usize, ModelError> {
+        let value = parse_attribute::<usize>(node, attribute)?
+            .or(maybe_default)
+            .ok_or_else(|| ModelError::missing_attribute(node, attribute))?;
+        if value >= size {
+            return Err(ModelError::invalid_attribute(
+                node,
+                attribute,
+                format!("outside the {grid_size} grid"),
+            ));
+        }
+        Ok(value)
+    };
+    let x = coordinate("x", width, None)?;
+    let y = coordinate("y", height, None)?;
+    // flat grids only have the one layer
+    let z = coordinate("z", length, (length == 1).then_some(0))?;
+
+    let symbol = parse_attribute::<char>(node, "value")?
+        .ok_or_else(|| ModelError::missing_attribute(node, "value"))?;
+    if let Some(values) = maybe_values {
+        check_symbols(node, "value", [symbol], values)?;
+    }
+
+    Ok((x, y, z, symbol))
+}
+
+fn required_attribute<'a>(node: &Node<'a, '_>, attribute: &str) -> Result<&'a str, ModelError> {
+    node.attribute(attribute)
+        .ok_or_else(|| ModelError::missing_attribute(node, attribute))
+}
+
+fn parse_attribute<T>(node: &Node, attribute: &str) -> Result<Option<T>, ModelError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    node.attribute(attribute)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err| ModelError::invalid_attribute(node, attribute, err))
+        })
+        .transpose()
+}
+
+/// Reads a flag written either as `True`/`False`, like the original models, or in lowercase.
+fn parse_flag(node: &Node, attribute: &str) -> Result<bool, ModelError> {
+    match node.attribute(attribute) {
+        None | Some("False" | "false") => Ok(false),
+        Some("True" | "true") => Ok(true),
+        Some(_) => Err(ModelError::invalid_attribute(
+            node,
+            attribute,
+            "expected True or False",
+        )),
+    }
+}
+
+/// Reads `wrap` with the axes to wrap around, e.g. `xy`, or `periodic="True"` for wrapping along
+/// all of them.
+fn parse_wrap(node: &Node) -> Result<Wrap, ModelError> {
+    let maybe_wrap = parse_attribute::<Wrap>(node, "wrap")?;
+    let periodic = parse_flag(node, "periodic")?;
 
-    let width = root.attribute("width").unwrap().parse().unwrap();
-    let height = root.attribute("height").unwrap().parse().unwrap();
-    let initial_fill = root.attribute("fill").unwrap().chars().next().unwrap();
+    match maybe_wrap {
+        Some(_) if node.has_attribute("periodic") => Err(ModelError::invalid_attribute(
+            node,
+            "wrap",
+            "`wrap` and `periodic` can't be used together",
+        )),
+        Some(wrap) => Ok(wrap),
+        None if periodic => Ok(Wrap::ALL),
+        None => Ok(Wrap::NONE),
+    }
+}
+
+/// Reads the `border` symbol, which can't be a grid symbol as well. Without it, input patterns
+/// have no border cells.
+fn parse_border(node: &Node, maybe_values: Option<&Alphabet>) -> Result<Option<char>, ModelError> {
+    let Some(border) = parse_attribute::<char>(node, "border")? else {
+        return Ok(None);
+    };
+
+    if is_reserved(border) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "border",
+            format!("'{border}' is reserved"),
+        ));
+    }
+    if maybe_values.is_some_and(|values| values.contains(border)) {
+        return Err(ModelError::invalid_attribute(
+            node,
+            "border",
+            format!("'{border}' is one of the root `values`, pick another `border`"),
+        ));
+    }
+    Ok(Some(border))
+}
+
+fn parse_dimension(
+    node: &Node,
+    attribute: &str,
+    maybe_override: Option<usize>,
+) -> Result<usize, ModelError> {
+    match maybe_override {
+        Some(0) => Err(ModelError::invalid_override(
+            attribute,
+            0,
+            "must be greater than zero",
+        )),
+        Some(value) => Ok(value),
+        None => match parse_attribute(node, attribute)? {
+            Some(0) => Err(ModelError::invalid_attribute(
+                node,
+                attribute,
+                "must be greater than zero",
+            )),
+            Some(value) => Ok(value),
+            None => Err(ModelError::missing_attribute(node, attribute)),
+        },
+    }
+}
 
-    (
-        MarkovJunior::new(initial_fill, width, height, seed),
-        parse_sequence(&root, true),
+/// Settings that are supplied by the caller rather than the model file.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ModelOptions {
+    /// Seed of the run set up by [`parse_xml_with_options`].
+    pub seed: Option<u64>,
+    /// Takes precedence over the root `width` attribute.
+    pub width: Option<usize>,
+    /// Takes precedence over the root `height` attribute.
+    pub height: Option<usize>,
+    /// Takes precedence over the root `length` attribute.
+    pub length: Option<usize>,
+}
+
+pub fn parse_xml(xml: &str, seed: Option<u64>) -> Result<(MarkovJunior, Sequence), ModelError> {
+    parse_xml_with_options(
+        xml,
+        &ModelOptions {
+            seed,
+            ..Default::default()
+        },
     )
 }
+
+/// Parses a model and sets up a run of it in one go.
+pub fn parse_xml_with_options(
+    xml: &str,
+    options: &ModelOptions,
+) -> Result<(MarkovJunior, Sequence), ModelError> {
+    let model = parse_model(xml, options)?;
+    Ok((model.initial_state(options.seed), model.root))
+}
+
+pub fn parse_model(xml: &str, options: &ModelOptions) -> Result<Model, ModelError> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let root = doc.root_element();
+
+    let width = parse_dimension(&root, "width", options.width)?;
+    let height = parse_dimension(&root, "height", options.height)?;
+    // grids are flat unless they have a `length`
+    let length = match (options.length, root.has_attribute("length")) {
+        (None, false) => 1,
+        _ => parse_dimension(&root, "length", options.length)?,
+    };
+    let initial_fill = parse_attribute::<char>(&root, "fill")?
+        .ok_or_else(|| ModelError::missing_attribute(&root, "fill"))?;
+    let maybe_values = parse_attribute::<Alphabet>(&root, "values")?;
+    if let Some(values) = &maybe_values {
+        check_symbols(&root, "fill", [initial_fill], values)?;
+    }
+    let origin = parse_flag(&root, "origin")?;
+    let wrap = parse_wrap(&root)?;
+    let border = parse_border(&root, maybe_values.as_ref())?;
+    let puts = root
+        .children()
+        .filter(|n| n.is_element() && n.tag_name().name() == "put")
+        .map(|n| parse_put(&n, [width, height, length], maybe_values.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let scope = Scope {
+        symmetry: if length > 1 {
+            Symmetry::CUBE
+        } else {
+            Symmetry::ALL
+        },
+        values: maybe_values.clone(),
+        border,
+        length,
+        ..Default::default()
+    };
+    let root_sequence = match root.tag_name().name() {
+        "sequence" => parse_sequence(&root, true, &scope)?,
+        // the root always runs once, so a markov root becomes the only child of that run
+        "markov" => Sequence {
+            vec: vec![RuleOrSequence::Markov(parse_sequence(
+                &root, false, &scope,
+            )?)],
+            steps: Some(1),
+        },
+        _ => return Err(ModelError::unknown_node(&root)),
+    };
+
+    // the symbol put at the origin would otherwise depend on the order of the document
+    if origin && maybe_values.is_none() {
+        return Err(ModelError::invalid_attribute(
+            &root,
+            "origin",
+            "the root needs `values`, whose second symbol is put at the origin",
+        ));
+    }
+
+    // without `values` the alphabet is every symbol the model uses, starting with `fill`
+    let alphabet = maybe_values.unwrap_or_else(|| {
+        Alphabet::from_symbols(
+            std::iter::once(initial_fill)
+                .chain(sequence_symbols(&root_sequence))
+                .chain(puts.iter().map(|&(_, _, _, symbol)| symbol)),
+        )
+    });
+
+    if origin && alphabet.len() < 2 {
+        return Err(ModelError::invalid_attribute(
+            &root,
+            "origin",
+            "the alphabet needs a second symbol to put at the origin",
+        ));
+    }
+
+    Ok(Model {
+        root: root_sequence,
+        width,
+        height,
+        length,
+        fill: initial_fill,
+        alphabet,
+        wrap,
+        origin,
+        puts,
+    })
+}
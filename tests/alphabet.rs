@@ -0,0 +1,21 @@
+use markov_junior::*;
+
+#[test]
+fn test_alphabet_from_symbols() {
+    let alphabet = Alphabet::from_symbols("BWBRW".chars());
+
+    assert_eq!(alphabet.symbols(), &['B', 'W', 'R']);
+    assert_eq!(alphabet.len(), 3);
+    assert_eq!(alphabet.index_of('R'), Some(2));
+    assert_eq!(alphabet.index_of('G'), None);
+}
+
+#[test]
+fn test_alphabet_closest() {
+    let alphabet = "BWRUGE".parse::<Alphabet>().unwrap();
+
+    assert_eq!(alphabet.closest('b'), Some('B'));
+    assert_eq!(alphabet.closest('F'), Some('G'));
+    assert_eq!(alphabet.closest('x'), Some('W'));
+    assert_eq!(Alphabet::default().closest('x'), None);
+}
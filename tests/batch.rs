@@ -0,0 +1,69 @@
+mod common;
+
+use common::load;
+use markov_junior::*;
+
+#[test]
+fn test_batch_matches_single_runs() {
+    let model = load("River", 24);
+    let seeds = [5, 1, 3];
+
+    let grids = run_batch(&model, &seeds, |run| (run.mj.seed, run.mj.grid));
+
+    for (&seed, (run_seed, grid)) in seeds.iter().zip(grids) {
+        let mut run = Run::new(model.clone(), Some(seed));
+        run.run();
+        assert_eq!(run_seed, seed);
+        assert_eq!(grid, run.mj.grid);
+    }
+}
+
+#[test]
+fn test_write_batch() {
+    let model = load("FireNoise", 16);
+    let dir = std::env::temp_dir().join(format!("markov_junior_batch_{}", std::process::id()));
+
+    let paths = write_batch(&model, "FireNoise", &[7, 8], &dir).unwrap();
+
+    assert_eq!(
+        paths,
+        vec![dir.join("FireNoise_7.txt"), dir.join("FireNoise_8.txt")]
+    );
+    for (seed, path) in [7, 8].into_iter().zip(&paths) {
+        let mut run = Run::new(model.clone(), Some(seed));
+        run.run();
+        let mut expected = Vec::new();
+        run.mj.write_grid(&mut expected).unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), expected);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_parse_seed_range() {
+    assert_eq!(parse_seed_range("3..10"), Ok(3..10));
+    assert!(parse_seed_range("10").is_err());
+    assert!(parse_seed_range("a..10").is_err());
+    assert_eq!(
+        parse_seed_range("5..2"),
+        Err(r#""5..2" holds no seed, its end has to be after its start"#.to_string())
+    );
+    assert!(parse_seed_range("5..5").is_err());
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn test_batch_is_independent_of_thread_count() {
+    let model = load("River", 24);
+    let seeds: Vec<u64> = (0..8).collect();
+
+    let grids_with = |threads| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| run_batch(&model, &seeds, |run| run.mj.grid))
+    };
+
+    assert_eq!(grids_with(1), grids_with(4));
+}
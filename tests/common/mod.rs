@@ -0,0 +1,12 @@
+use markov_junior::*;
+use std::sync::Arc;
+
+/// Built-in model `name` on a `size` by `size` grid.
+pub fn load(name: &str, size: usize) -> Arc<Model> {
+    let options = ModelOptions {
+        width: Some(size),
+        height: Some(size),
+        ..Default::default()
+    };
+    Arc::new(load_model(name, "does-not-exist", &options).unwrap())
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::load;
+use markov_junior::*;
+use std::sync::Arc;
+
+#[test]
+fn test_interpreter_matches_apply_sequence() {
+    for name in ["River", "FireNoise", "Test"] {
+        let model = load(name, 24);
+        let mut run = Run::new(model.clone(), Some(3));
+        run.run();
+        let mj = run.mj;
+
+        let mut interpreter = Interpreter::new(Run::new(model, Some(3)));
+        let changes = interpreter.by_ref().map(|step| step.changes).sum::<usize>();
+        let interpreted = interpreter.into_run().mj;
+
+        assert_eq!(interpreted.grid_symbols(), mj.grid_symbols(), "{name}");
+        assert_eq!(interpreted.changes, mj.changes, "{name}");
+        assert_eq!(changes, interpreted.changed_cells, "{name}");
+    }
+}
+
+#[test]
+fn test_interpreter_steps() {
+    let xml = r#"
+        <sequence fill="B" width="3" height="1">
+          <one in="B" out="R" steps="1"/>
+          <markov>
+            <all in="RB" out="RW"/>
+            <one in="W" out="R"/>
+          </markov>
+        </sequence>
+    "#;
+    let model = parse_model(xml, &ModelOptions::default()).unwrap();
+    let mut interpreter = Interpreter::new(Run::new(Arc::new(model), Some(0)));
+
+    let step = interpreter.step();
+    assert_eq!(step.node, Some(vec![0]));
+    assert_eq!(step.changes, 1);
+    assert!(!step.finished);
+    assert_eq!(
+        interpreter
+            .mj()
+            .grid_symbols()
+            .iter()
+            .filter(|&&c| c == 'R')
+            .count(),
+        1
+    );
+
+    let nodes = interpreter
+        .by_ref()
+        .map(|step| step.node.unwrap())
+        .collect::<Vec<_>>();
+    for node in &nodes {
+        assert!(matches!(
+            interpreter.root().node(node),
+            Some(RuleOrSequence::Rule(_))
+        ));
+        assert_eq!(node[0], 1);
+    }
+    assert!(nodes.contains(&vec![1, 0]) && nodes.contains(&vec![1, 1]));
+
+    assert!(interpreter.is_finished());
+    assert_eq!(interpreter.mj().grid_symbols(), vec!['R'; 3]);
+    assert_eq!(
+        interpreter.step(),
+        StepResult {
+            node: None,
+            changes: 0,
+            finished: true,
+        }
+    );
+}
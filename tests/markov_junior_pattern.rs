@@ -18,13 +18,19 @@ fn test_pattern_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "EF/HI");
 
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), None);
+    assert_eq!(mj.pattern_fits(0, 0, 0, &pattern), None);
     assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), None);
-    assert_eq!(mj.pattern_fits(1, 1, &pattern), Some(1));
-    assert_eq!(mj.pattern_fits_canonical(1, 1, &pattern), Some(1));
-    assert_eq!(mj.pattern_fits(1, 0, &pattern), None);
+    assert_eq!(
+        mj.pattern_fits(1, 1, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(1, 1, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(mj.pattern_fits(1, 0, 0, &pattern), None);
     assert_eq!(mj.pattern_fits_canonical(1, 0, &pattern), None);
-    assert_eq!(mj.pattern_fits(0, 1, &pattern), None);
+    assert_eq!(mj.pattern_fits(0, 1, 0, &pattern), None);
     assert_eq!(mj.pattern_fits_canonical(0, 1, &pattern), None);
 }
 
@@ -32,64 +38,109 @@ fn test_pattern_fits_canonical() {
 fn test_pattern_0_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "AB/DE");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(1));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(1));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::Identity)
+    );
 }
 
 #[test]
 fn test_pattern_90_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "DA/EB");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(4));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(4));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Rotate270)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::Rotate270)
+    );
 }
 
 #[test]
 fn test_pattern_180_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "ED/BA");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(3));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(3));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Rotate180)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::Rotate180)
+    );
 }
 
 #[test]
 fn test_pattern_270_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "BE/AD");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(2));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(2));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Rotate90)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::Rotate90)
+    );
 }
 
 #[test]
 fn test_pattern_mirror_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "BA/ED");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(-1));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(-1));
+    assert_eq!(mj.pattern_fits(0, 0, 0, &pattern), Some(Transform::Mirror));
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::Mirror)
+    );
 }
 
 #[test]
 fn test_pattern_mirror_90_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "EB/DA");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(-2));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(-2));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::MirrorRotate90)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::MirrorRotate90)
+    );
 }
 
 #[test]
 fn test_pattern_mirror_180_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "DE/AB");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(-3));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(-3));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::MirrorRotate180)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::MirrorRotate180)
+    );
 }
 
 #[test]
 fn test_pattern_mirror_270_fits_canonical() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
     let pattern = precompute_pattern(&mut mj, "AD/BE");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(-4));
-    assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(-4));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::MirrorRotate270)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::MirrorRotate270)
+    );
 }
 
 #[test]
@@ -105,9 +156,9 @@ fn test_pattern_fits_canonical_2() {
         let mut mj = MarkovJunior::new_grid("ABCD", 2, 2, None);
         let pattern = precompute_pattern(&mut mj, &pattern_line);
 
-        assert_ne!(mj.pattern_fits(0, 0, &pattern), None);
+        assert_ne!(mj.pattern_fits(0, 0, 0, &pattern), None);
         assert_eq!(
-            mj.pattern_fits(0, 0, &pattern),
+            mj.pattern_fits(0, 0, 0, &pattern),
             mj.pattern_fits_canonical(0, 0, &pattern)
         );
 
@@ -116,9 +167,9 @@ fn test_pattern_fits_canonical_2() {
         let mut mj = MarkovJunior::new_grid(&grid_data, 2, 2, None);
         let pattern = precompute_pattern(&mut mj, line);
 
-        assert_ne!(mj.pattern_fits(0, 0, &pattern), None);
+        assert_ne!(mj.pattern_fits(0, 0, 0, &pattern), None);
         assert_eq!(
-            mj.pattern_fits(0, 0, &pattern),
+            mj.pattern_fits(0, 0, 0, &pattern),
             mj.pattern_fits_canonical(0, 0, &pattern)
         );
 
@@ -128,9 +179,9 @@ fn test_pattern_fits_canonical_2() {
             let mut mj = MarkovJunior::new_grid(&grid_data2, 2, 2, None);
             let pattern = precompute_pattern(&mut mj, &pattern_line);
 
-            assert_ne!(mj.pattern_fits(0, 0, &pattern), None);
+            assert_ne!(mj.pattern_fits(0, 0, 0, &pattern), None);
             assert_eq!(
-                mj.pattern_fits(0, 0, &pattern),
+                mj.pattern_fits(0, 0, 0, &pattern),
                 mj.pattern_fits_canonical(0, 0, &pattern)
             );
         }
@@ -141,8 +192,8 @@ fn test_pattern_fits_canonical_2() {
 // fn test_pattern_fits_canonical_4() {
 //     let mut mj = MarkovJunior::new_grid("UBBU", 2, 2, None);
 //     let pattern = set_pattern(&mut mj, "BU/UB");
-//     assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(2));
-//     assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(2));
+//     assert_eq!(mj.pattern_fits(0, 0, 0, &pattern), Some(Transform::Rotate90));
+//     assert_eq!(mj.pattern_fits_canonical(0, 0, &pattern), Some(Transform::Rotate90));
 // }
 
 #[test]
@@ -150,22 +201,40 @@ fn test_pattern_fits() {
     let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
 
     let pattern = precompute_pattern(&mut mj, "AB");
-    assert_eq!(mj.pattern_fits(0, 0, &pattern), Some(1));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
 
     let pattern = precompute_pattern(&mut mj, "BC");
-    assert_eq!(mj.pattern_fits(1, 0, &pattern), Some(1));
+    assert_eq!(
+        mj.pattern_fits(1, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
 
     let pattern = precompute_pattern(&mut mj, "EF");
-    assert_eq!(mj.pattern_fits(1, 1, &pattern), Some(1));
+    assert_eq!(
+        mj.pattern_fits(1, 1, 0, &pattern),
+        Some(Transform::Identity)
+    );
 
     let pattern = precompute_pattern(&mut mj, "HI");
-    assert_eq!(mj.pattern_fits(1, 2, &pattern), Some(1));
+    assert_eq!(
+        mj.pattern_fits(1, 2, 0, &pattern),
+        Some(Transform::Identity)
+    );
 
     let pattern = precompute_pattern(&mut mj, "FI");
-    assert_eq!(mj.pattern_fits(2, 1, &pattern), Some(2));
+    assert_eq!(
+        mj.pattern_fits(2, 1, 0, &pattern),
+        Some(Transform::Rotate90)
+    );
 
     let pattern = precompute_pattern(&mut mj, "IF");
-    assert_eq!(mj.pattern_fits(2, 1, &pattern), Some(4));
+    assert_eq!(
+        mj.pattern_fits(2, 1, 0, &pattern),
+        Some(Transform::Rotate270)
+    );
 }
 
 #[test]
@@ -173,99 +242,99 @@ fn test_apply_canonical_pattern() {
     let mut mj = MarkovJunior::new('.', 5, 5, None);
     let pattern = Pattern::new("AB/CD");
 
-    mj.apply_pattern(1, 1, &pattern, 1);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Identity);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'A', b'B', b'.', b'.',
-            b'.', b'C', b'D', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'A', 'B', '.', '.',
+            '.', 'C', 'D', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
-    mj.apply_pattern(1, 1, &pattern, 2);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate90);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'C', b'A', b'.', b'.',
-            b'.', b'D', b'B', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'C', 'A', '.', '.',
+            '.', 'D', 'B', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
-    mj.apply_pattern(1, 1, &pattern, 3);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate180);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'D', b'C', b'.', b'.',
-            b'.', b'B', b'A', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'D', 'C', '.', '.',
+            '.', 'B', 'A', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
-    mj.apply_pattern(1, 1, &pattern, 4);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate270);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'B', b'D', b'.', b'.',
-            b'.', b'A', b'C', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'B', 'D', '.', '.',
+            '.', 'A', 'C', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
-    mj.apply_pattern(1, 1, &pattern, -1);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Mirror);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'B', b'A', b'.', b'.',
-            b'.', b'D', b'C', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'B', 'A', '.', '.',
+            '.', 'D', 'C', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
-    mj.apply_pattern(1, 1, &pattern, -2);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::MirrorRotate90);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'D', b'B', b'.', b'.',
-            b'.', b'C', b'A', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'D', 'B', '.', '.',
+            '.', 'C', 'A', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
-    mj.apply_pattern(1, 1, &pattern, -3);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::MirrorRotate180);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'C', b'D', b'.', b'.',
-            b'.', b'A', b'B', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'C', 'D', '.', '.',
+            '.', 'A', 'B', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
-    mj.apply_pattern(1, 1, &pattern, -4);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::MirrorRotate270);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'A', b'C', b'.', b'.',
-            b'.', b'B', b'D', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'A', 'C', '.', '.',
+            '.', 'B', 'D', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 }
@@ -275,54 +344,54 @@ fn test_apply_wildcard_square_pattern() {
     let pattern = Pattern::new("A*/C*");
 
     let mut mj = MarkovJunior::new('.', 5, 5, None);
-    mj.apply_pattern(1, 1, &pattern, 1);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Identity);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'A', b'.', b'.', b'.',
-            b'.', b'C', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'A', '.', '.', '.',
+            '.', 'C', '.', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 5, 5, None);
-    mj.apply_pattern(1, 1, &pattern, 2);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate90);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'C', b'A', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', 'C', 'A', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 5, 5, None);
-    mj.apply_pattern(1, 1, &pattern, 3);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate180);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'C', b'.', b'.',
-            b'.', b'.', b'A', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', '.', 'C', '.', '.',
+            '.', '.', 'A', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 5, 5, None);
-    mj.apply_pattern(1, 1, &pattern, 4);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate270);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'A', b'C', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', 'A', 'C', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
         ]
     );
 }
@@ -332,50 +401,50 @@ fn test_apply_horizontal_pattern() {
     let pattern = Pattern::new("AB");
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 1);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Identity);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'A', b'B', b'.',
-            b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'A', 'B', '.',
+            '.', '.', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 2);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate90);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'A', b'.', b'.',
-            b'.', b'B', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'A', '.', '.',
+            '.', 'B', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 3);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate180);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'B', b'A', b'.',
-            b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'B', 'A', '.',
+            '.', '.', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 4);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate270);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'B', b'.', b'.',
-            b'.', b'A', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'B', '.', '.',
+            '.', 'A', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 }
@@ -385,50 +454,50 @@ fn test_apply_vertical_pattern() {
     let pattern = Pattern::new("A/B");
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 1);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Identity);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'A', b'.', b'.',
-            b'.', b'B', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'A', '.', '.',
+            '.', 'B', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 2);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate90);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'B', b'A', b'.',
-            b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'B', 'A', '.',
+            '.', '.', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 3);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate180);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'B', b'.', b'.',
-            b'.', b'A', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'B', '.', '.',
+            '.', 'A', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 
     let mut mj = MarkovJunior::new('.', 4, 4, None);
-    mj.apply_pattern(1, 1, &pattern, 4);
+    mj.apply_pattern(1, 1, 0, &pattern, Transform::Rotate270);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.',
-            b'.', b'A', b'B', b'.',
-            b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.',
+            '.', '.', '.', '.',
+            '.', 'A', 'B', '.',
+            '.', '.', '.', '.',
+            '.', '.', '.', '.',
         ]
     );
 }
@@ -438,16 +507,569 @@ fn test_apply_pattern_at_edge() {
     let mut mj = MarkovJunior::new('.', 5, 5, None);
     let pattern = Pattern::new("AB/CD");
 
-    mj.apply_pattern(3, 3, &pattern, 1);
+    mj.apply_pattern(3, 3, 0, &pattern, Transform::Identity);
+
+    assert_eq!(
+        mj.grid_symbols(),
+        #[rustfmt::skip] vec![
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', 'A', 'B',
+            '.', '.', '.', 'C', 'D',
+        ]
+    );
+}
+
+#[test]
+fn test_pattern_fits_with_symmetry() {
+    let mut mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
+    let pattern = precompute_pattern(&mut mj, "DA/EB");
+
+    let restricted = pattern.clone().with_symmetry("()".parse().unwrap());
+    assert_eq!(mj.pattern_fits(0, 0, 0, &restricted), None);
+    assert_eq!(mj.pattern_fits_canonical(0, 0, &restricted), None);
+
+    let restricted = pattern.clone().with_symmetry("(xy+)".parse().unwrap());
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &restricted),
+        Some(Transform::Rotate270)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &restricted),
+        Some(Transform::Rotate270)
+    );
+
+    let restricted = pattern.with_symmetry("(x)(y)".parse().unwrap());
+    assert_eq!(mj.pattern_fits(0, 0, 0, &restricted), None);
+    assert_eq!(mj.pattern_fits_canonical(0, 0, &restricted), None);
+}
+
+#[test]
+fn test_pattern_fits_with_symmetry_alias() {
+    // "AA/BB" is mirror-symmetric, so matching it upside down is allowed under "(y)"
+    // even though the canonical forms relate the grid and pattern by a 180° rotation
+    let mut mj = MarkovJunior::new_grid("BBAA", 2, 2, None);
+    let pattern = precompute_pattern(&mut mj, "AA/BB").with_symmetry("(y)".parse().unwrap());
+
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::MirrorRotate180)
+    );
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &pattern),
+        Some(Transform::MirrorRotate180)
+    );
+}
+
+#[test]
+fn test_pattern_fits_with_unions() {
+    let unions = [('?', vec!['A', 'E'])].into_iter().collect();
+    let mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
+    let pattern = Pattern::new_input("?B/DE", &unions).unwrap();
+
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(mj.pattern_fits(1, 1, 0, &pattern), None);
+
+    // "?" is matched against "E" after rotating by 180°
+    let pattern = Pattern::new_input("?D/BA", &unions).unwrap();
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Rotate180)
+    );
+}
+
+#[test]
+fn test_pattern_fits_with_character_classes() {
+    let mj = MarkovJunior::new_grid("ABCDEFGHI", 3, 3, None);
+    let unions = Default::default();
+
+    let pattern = Pattern::new_input("[AE]!C", &unions).unwrap();
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(
+        mj.pattern_fits(1, 1, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    // "!C" rules out "EF" read right to left, but "[AE]" still matches "E" below "B"
+    assert_eq!(
+        mj.pattern_fits(1, 0, 0, &pattern),
+        Some(Transform::Rotate270)
+    );
+    assert_eq!(mj.pattern_fits(2, 0, 0, &pattern), None);
+
+    let pattern = Pattern::new_input("[^AI]", &unions).unwrap();
+    assert_eq!(mj.pattern_fits(0, 0, 0, &pattern), None);
+    assert_eq!(
+        mj.pattern_fits(1, 1, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(mj.pattern_fits(2, 2, 0, &pattern), None);
+}
+
+#[test]
+fn test_unicode_symbols() {
+    let mut mj = MarkovJunior::new_grid("─│🌊─", 2, 2, None);
+    assert_eq!(mj.alphabet.symbols(), &['─', '│', '🌊']);
+
+    let pattern = Pattern::new("─🌊");
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Rotate90)
+    );
+    assert_eq!(
+        mj.pattern_fits(0, 1, 0, &pattern),
+        Some(Transform::Rotate180)
+    );
+
+    mj.apply_pattern(0, 1, 0, &Pattern::new("❌┼"), Transform::Identity);
+    assert_eq!(mj.grid_symbols(), vec!['─', '│', '❌', '┼']);
+    assert_eq!(mj.alphabet.symbols(), &['─', '│', '🌊', '❌', '┼']);
+}
+
+#[test]
+fn test_full_grid_scan_matches_cell_by_cell() {
+    // tall enough for the scan to run in parallel with the `parallel` feature
+    let (width, height) = (37, 41);
+    let data = (0..width * height)
+        .map(|i| ['A', 'B', 'C'][(i * 7 + i / width * 13 + i * i / 5) % 3])
+        .collect::<String>();
+    let mut mj = MarkovJunior::new_grid(&data, width, height, None);
+    let rule = Rule {
+        patterns: vec![
+            PatternRule::new(Pattern::new("AB/CA"), Pattern::new("BB/BB"), None),
+            PatternRule::new(Pattern::new("AB*"), Pattern::new("CCC"), None),
+        ],
+        kind: RuleKind::One,
+        steps: None,
+    };
+
+    mj.precompute_canonical_forms(&rule);
+    let cache = mj.compute_cache(&rule);
+
+    assert!(!cache.is_empty());
+    for y in 0..height {
+        for x in 0..width {
+            assert!(cache
+                .cell(x, y, 0)
+                .eq(&mj.cell_matches(&rule, x, y, 0).collect::<Vec<_>>()));
+        }
+    }
+}
+
+#[test]
+fn test_pattern_fits_canonical_with_symbols_sharing_codes() {
+    // 'Ł' is U+0141, which packs to the same byte as 'B'
+    assert_eq!(symbol_code('Ł'), symbol_code('B'));
+    let mut mj = MarkovJunior::new_grid("ŁŁAB", 2, 2, None);
+
+    for line in ["BB", "ŁŁ", "B*", "*Ł", "AB"] {
+        let pattern = Pattern::new(line);
+        let rule = Rule {
+            patterns: vec![PatternRule::new(pattern.clone(), pattern.clone(), None)],
+            kind: RuleKind::One,
+            steps: None,
+        };
+        mj.precompute_canonical_forms(&rule);
+
+        for y in 0..2 {
+            for x in 0..2 {
+                assert_eq!(
+                    mj.pattern_fits_canonical(x, y, &pattern),
+                    mj.pattern_fits(x, y, 0, &pattern),
+                    "{line} at {x}, {y}"
+                );
+            }
+        }
+    }
+    assert_eq!(mj.pattern_fits_canonical(0, 0, &Pattern::new("BB")), None);
+    assert_eq!(
+        mj.pattern_fits_canonical(0, 0, &Pattern::new("ŁŁ")),
+        Some(Transform::Identity)
+    );
+}
+
+/// Grid of `symbols` that looks random enough to match patterns in many different ways.
+fn scrambled_grid(symbols: &[char], width: usize, height: usize) -> MarkovJunior {
+    let data = (0..width * height)
+        .map(|i| symbols[(i * i / 3 + i / width * 5 + i * 11) % symbols.len()])
+        .collect::<String>();
+    MarkovJunior::new_grid(&data, width, height, None)
+}
+
+#[test]
+fn test_pattern_fits_canonical_matches_pattern_fits() {
+    let lines = [
+        "AB",
+        "A/B",
+        "AAB",
+        "AB/BA",
+        "AB/GA",
+        "ABG/GAB",
+        "AG/GB/BA",
+        "ABBA/GAGA",
+        "A*",
+        "A*/*G",
+        "*G*/GAG",
+        "*AA*/AGGA/*AA*",
+        "A*B",
+        "AA/**",
+        // too large to pack, so matched by canonical ids
+        "ABGAB/GABGA/BGABG/AGGAB",
+    ];
+    let symmetries = ["(xy)", "(xy+)", "(x)", "()"];
+    let mut mj = scrambled_grid(&['A', 'B', 'G'], 23, 19);
+    let mut fits = 0;
+
+    for line in lines {
+        for symmetry in symmetries {
+            let pattern = Pattern::new(line).with_symmetry(symmetry.parse().unwrap());
+            assert!(pattern.canonical_key.is_some(), "{line}");
+            let rule = Rule {
+                patterns: vec![PatternRule::new(pattern.clone(), pattern.clone(), None)],
+                kind: RuleKind::One,
+                steps: None,
+            };
+            mj.precompute_canonical_forms(&rule);
+
+            for y in 0..mj.height {
+                for x in 0..mj.width {
+                    let expected = mj.pattern_fits(x, y, 0, &pattern);
+                    assert_eq!(
+                        mj.pattern_fits_canonical(x, y, &pattern),
+                        expected,
+                        "{line} {symmetry} at {x}, {y}"
+                    );
+                    fits += expected.is_some() as usize;
+                }
+            }
+        }
+    }
+
+    assert!(fits > 100);
+}
+
+#[test]
+fn test_canonical_forms_stay_up_to_date() {
+    let mut mj = scrambled_grid(&['A', 'B', 'G'], 17, 13);
+    // the single cell pattern has no canonical key but changes the forms of the others
+    let rule = Rule {
+        patterns: vec![
+            PatternRule::new(Pattern::new("AB"), Pattern::new("BB"), None),
+            PatternRule::new(Pattern::new("G"), Pattern::new("A"), None),
+            PatternRule::new(Pattern::new("*BB*/B**B"), Pattern::new("****/*AG*"), None),
+        ],
+        kind: RuleKind::One,
+        steps: Some(40),
+    };
+
+    assert!(mj.apply_rule(&rule));
+
+    let canonical_forms = mj.canonical_forms.clone();
+    mj.precompute_canonical_forms(&rule);
+    assert_eq!(canonical_forms, mj.canonical_forms);
+}
+
+#[test]
+fn test_canonical_forms_of_earlier_rules_are_dropped() {
+    let mut mj = scrambled_grid(&['A', 'B', 'G'], 17, 13);
+    let first = Rule {
+        patterns: vec![PatternRule::new(
+            Pattern::new("AB/BG"),
+            Pattern::new("G*/**"),
+            None,
+        )],
+        kind: RuleKind::One,
+        steps: Some(5),
+    };
+    let second = Rule {
+        patterns: vec![PatternRule::new(
+            Pattern::new("GB"),
+            Pattern::new("AA"),
+            None,
+        )],
+        kind: RuleKind::One,
+        steps: Some(20),
+    };
+
+    assert!(mj.apply_rule(&first));
+    assert!(mj.apply_rule(&second));
+    mj.put(3, 4, 0, 'B');
+
+    let mut expected = MarkovJunior::new_grid(
+        &mj.grid_symbols().into_iter().collect::<String>(),
+        mj.width,
+        mj.height,
+        None,
+    );
+    expected.precompute_canonical_forms(&second);
+    assert_eq!(mj.canonical_forms, expected.canonical_forms);
+    assert!(mj
+        .canonical_forms
+        .keys()
+        .eq([&CanonicalKey { long: 2, short: 1 }]));
+}
+
+#[test]
+fn test_apply_pattern_wraps_around_edges() {
+    let mut mj = MarkovJunior::new('.', 5, 4, None).with_wrap(Wrap::ALL);
+    let pattern = Pattern::new("AB/CD");
+
+    mj.apply_pattern(4, 3, 0, &pattern, Transform::Identity);
 
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'.', b'.',
-            b'.', b'.', b'.', b'A', b'B',
-            b'.', b'.', b'.', b'C', b'D',
+            'D', '.', '.', '.', 'C',
+            '.', '.', '.', '.', '.',
+            '.', '.', '.', '.', '.',
+            'B', '.', '.', '.', 'A',
         ]
     );
 }
+
+#[test]
+fn test_pattern_fits_wraps_around_edges() {
+    let grid = "BAAB\
+                GGGG\
+                AGGG";
+    let pattern = Pattern::new("BB").with_symmetry("()".parse().unwrap());
+    let vertical = Pattern::new("A/B").with_symmetry("()".parse().unwrap());
+
+    let mj = MarkovJunior::new_grid(grid, 4, 3, None);
+    assert_eq!(mj.pattern_fits(3, 0, 0, &pattern), None);
+    assert_eq!(mj.pattern_fits(0, 2, 0, &vertical), None);
+
+    let mj = MarkovJunior::new_grid(grid, 4, 3, None).with_wrap("x".parse().unwrap());
+    assert_eq!(
+        mj.pattern_fits(3, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(mj.pattern_fits(0, 2, 0, &vertical), None);
+
+    let mj = MarkovJunior::new_grid(grid, 4, 3, None).with_wrap("y".parse().unwrap());
+    assert_eq!(mj.pattern_fits(3, 0, 0, &pattern), None);
+    assert_eq!(
+        mj.pattern_fits(0, 2, 0, &vertical),
+        Some(Transform::Identity)
+    );
+}
+
+#[test]
+fn test_pattern_fits_canonical_matches_pattern_fits_when_wrapping() {
+    let lines = [
+        "AB",
+        "AB/GA",
+        "ABG/GAB",
+        "AG/GB/BA",
+        "ABBA/GAGA",
+        "A*/*G",
+        "*AA*/AGGA/*AA*",
+    ];
+    let mut fits = 0;
+
+    for wrap in ["x", "y", "xy"] {
+        let mut mj = scrambled_grid(&['A', 'B', 'G'], 11, 7).with_wrap(wrap.parse().unwrap());
+
+        for line in lines {
+            let pattern = Pattern::new(line).with_symmetry("(x)".parse().unwrap());
+            let rule = Rule {
+                patterns: vec![PatternRule::new(pattern.clone(), pattern.clone(), None)],
+                kind: RuleKind::One,
+                steps: None,
+            };
+            mj.precompute_canonical_forms(&rule);
+
+            for y in 0..mj.height {
+                for x in 0..mj.width {
+                    let expected = mj.pattern_fits(x, y, 0, &pattern);
+                    assert_eq!(
+                        mj.pattern_fits_canonical(x, y, &pattern),
+                        expected,
+                        "{line} {wrap} at {x}, {y}"
+                    );
+                    fits += expected.is_some() as usize;
+                }
+            }
+        }
+    }
+
+    assert!(fits > 20);
+}
+
+#[test]
+fn test_canonical_forms_stay_up_to_date_when_wrapping() {
+    let mut mj = scrambled_grid(&['A', 'B', 'G'], 9, 7).with_wrap(Wrap::ALL);
+    let rule = Rule {
+        patterns: vec![
+            PatternRule::new(Pattern::new("AB"), Pattern::new("BB"), None),
+            PatternRule::new(Pattern::new("G"), Pattern::new("A"), None),
+            PatternRule::new(Pattern::new("*BB*/B**B"), Pattern::new("****/*AG*"), None),
+        ],
+        kind: RuleKind::One,
+        steps: Some(40),
+    };
+
+    assert!(mj.apply_rule(&rule));
+
+    let canonical_forms = mj.canonical_forms.clone();
+    mj.canonical_forms.clear();
+    mj.precompute_canonical_forms(&rule);
+    assert_eq!(canonical_forms, mj.canonical_forms);
+}
+
+fn border_rule(lines: &[&str]) -> Rule {
+    Rule {
+        patterns: lines
+            .iter()
+            .map(|line| {
+                let pattern =
+                    Pattern::new_input_with_border(line, &Default::default(), BORDER).unwrap();
+                PatternRule::new(pattern.clone(), pattern, None)
+            })
+            .collect(),
+        kind: RuleKind::One,
+        steps: None,
+    }
+}
+
+#[test]
+fn test_pattern_fits_border() {
+    let mut mj = MarkovJunior::new_grid("WBBBBW", 3, 2, None);
+    let rule = border_rule(&["#W", "W*#"]);
+    let (left, right) = (&rule.patterns[0].input, &rule.patterns[1].input);
+
+    mj.precompute_canonical_forms(&rule);
+    assert_eq!(mj.margin, 2);
+
+    // columns past the grid and its margin stand for the ones before the grid
+    let before_grid = mj.width + mj.margin - 1;
+    assert_eq!(
+        mj.pattern_fits(before_grid, 0, 0, left),
+        Some(Transform::Identity)
+    );
+    assert_eq!(mj.pattern_fits(before_grid, 1, 0, left), None);
+    assert_eq!(mj.pattern_fits(2, 1, 0, left), Some(Transform::Rotate180));
+    assert_eq!(mj.pattern_fits(0, 0, 0, left), None);
+    assert_eq!(mj.pattern_fits(0, 0, 0, right), Some(Transform::Rotate90));
+    // `*` cells don't hang off the grid
+    assert_eq!(mj.pattern_fits(2, 1, 0, right), None);
+}
+
+#[test]
+fn test_pattern_fits_canonical_matches_pattern_fits_with_border() {
+    let rule = border_rule(&[
+        "#A", "#AB", "#B/#A", "#AB/#BG", "##G/##A", "A#", "#A*/#*G", "#*G", "*#",
+    ]);
+    let mut fits = 0;
+
+    for wrap in [Wrap::NONE, "x".parse().unwrap()] {
+        let mut mj = scrambled_grid(&['A', 'B', 'G'], 9, 7).with_wrap(wrap);
+        mj.precompute_canonical_forms(&rule);
+        let anchor_width = if wrap.x {
+            mj.width
+        } else {
+            mj.width + mj.margin
+        };
+
+        for pattern_rule in &rule.patterns {
+            let pattern = &pattern_rule.input;
+            assert!(pattern.canonical_key.is_some(), "{}", pattern.line);
+
+            for y in 0..mj.height + mj.margin {
+                for x in 0..anchor_width {
+                    let expected = mj.pattern_fits(x, y, 0, pattern);
+                    assert_eq!(
+                        mj.pattern_fits_canonical(x, y, pattern),
+                        expected,
+                        "{} {wrap:?} at {x}, {y}",
+                        pattern.line
+                    );
+                    fits += expected.is_some() as usize;
+                }
+            }
+        }
+    }
+
+    assert!(fits > 20);
+}
+
+#[test]
+fn test_canonical_forms_stay_up_to_date_with_border() {
+    let mut mj = scrambled_grid(&['A', 'B', 'G'], 9, 7);
+    let rule = Rule {
+        patterns: vec![
+            PatternRule::new(Pattern::new("AB"), Pattern::new("BB"), None),
+            PatternRule::new(
+                Pattern::new_input_with_border("#G", &Default::default(), BORDER).unwrap(),
+                Pattern::new("*A"),
+                None,
+            ),
+            PatternRule::new(
+                Pattern::new_input_with_border("#A*/#*B", &Default::default(), BORDER).unwrap(),
+                Pattern::new("*G*/*AG"),
+                None,
+            ),
+        ],
+        kind: RuleKind::One,
+        steps: Some(40),
+    };
+
+    assert!(mj.apply_rule(&rule));
+
+    let canonical_forms = mj.canonical_forms.clone();
+    mj.canonical_forms.clear();
+    mj.precompute_canonical_forms(&rule);
+    assert_eq!(canonical_forms, mj.canonical_forms);
+}
+
+#[test]
+fn test_pattern_fits_in_layers() {
+    let mj = MarkovJunior::new_grid("ABCDEFGHIJKL", 3, 2, None);
+    assert_eq!(mj.length, 2);
+
+    let pattern = Pattern::new("H B");
+    assert_eq!(
+        mj.pattern_fits(1, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(mj.pattern_fits(1, 0, 1, &pattern), None);
+    let pattern = Pattern::new("GH/JK AB/DE");
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
+    assert_eq!(mj.pattern_fits(1, 0, 0, &pattern), None);
+
+    // every layer of a pattern turns the same way
+    let pattern = Pattern::new("KH EB");
+    assert_eq!(
+        mj.pattern_fits(1, 0, 0, &pattern),
+        Some(Transform::Rotate270)
+    );
+
+    // a flat pattern stood up along z
+    let pattern = Pattern::new("AG").tilted(Tilt::XUp);
+    assert_eq!((pattern.width, pattern.depth), (1, 2));
+    assert_eq!(
+        mj.pattern_fits(0, 0, 0, &pattern),
+        Some(Transform::Identity)
+    );
+}
+
+#[test]
+fn test_apply_pattern_in_layers() {
+    let mut mj = MarkovJunior::new_3d('.', 2, 2, 2, None);
+    let pattern = Pattern::new("**/CD AB/**");
+
+    mj.apply_pattern(0, 0, 0, &pattern, Transform::Rotate90);
+    assert_eq!(
+        mj.grid_symbols().into_iter().collect::<String>(),
+        ".A.BC.D."
+    );
+}
@@ -1,5 +1,9 @@
 #![feature(stmt_expr_attributes)]
 use markov_junior::*;
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
 
 fn rule_to_sequence<I>(rule_or_rules: I) -> Sequence
 where
@@ -16,11 +20,12 @@ where
 #[test]
 fn test_generate() {
     let mut mj = MarkovJunior::new('.', 3, 3, None);
-    mj.grid = #[rustfmt::skip] vec![
-        b'B', b'W', b'G',
-        b'B', b'W', b'G',
-        b'B', b'W', b'G'
-    ];
+    #[rustfmt::skip]
+    mj.set_grid_symbols(&[
+        'B', 'W', 'G',
+        'B', 'W', 'G',
+        'B', 'W', 'G'
+    ]);
     let sequence = rule_to_sequence(Rule {
         patterns: vec![PatternRule::new(
             Pattern::new("BW"),
@@ -34,11 +39,11 @@ fn test_generate() {
     mj.apply_sequence(&sequence, true);
 
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'W', b'W', b'G',
-            b'W', b'W', b'G',
-            b'W', b'W', b'G'
+            'W', 'W', 'G',
+            'W', 'W', 'G',
+            'W', 'W', 'G'
         ]
     );
 }
@@ -46,11 +51,12 @@ fn test_generate() {
 #[test]
 fn test_generate_2() {
     let mut mj = MarkovJunior::new('.', 3, 3, None);
-    mj.grid = #[rustfmt::skip] vec![
-        b'B', b'W', b'G',
-        b'B', b'W', b'G',
-        b'B', b'W', b'G'
-    ];
+    #[rustfmt::skip]
+    mj.set_grid_symbols(&[
+        'B', 'W', 'G',
+        'B', 'W', 'G',
+        'B', 'W', 'G'
+    ]);
 
     let sequence = rule_to_sequence(Rule {
         patterns: vec![PatternRule::new(
@@ -64,11 +70,11 @@ fn test_generate_2() {
     mj.apply_sequence(&sequence, true);
 
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'B', b'W', b'R',
-            b'B', b'W', b'R',
-            b'B', b'W', b'R'
+            'B', 'W', 'R',
+            'B', 'W', 'R',
+            'B', 'W', 'R'
         ]
     );
 }
@@ -76,11 +82,12 @@ fn test_generate_2() {
 #[test]
 fn test_generate_3() {
     let mut mj = MarkovJunior::new('.', 3, 3, None);
-    mj.grid = #[rustfmt::skip] vec![
-        b'B', b'W', b'G',
-        b'B', b'W', b'G',
-        b'B', b'W', b'G'
-    ];
+    #[rustfmt::skip]
+    mj.set_grid_symbols(&[
+        'B', 'W', 'G',
+        'B', 'W', 'G',
+        'B', 'W', 'G'
+    ]);
     let sequence = rule_to_sequence(vec![
         Rule {
             patterns: vec![PatternRule::new(
@@ -104,11 +111,11 @@ fn test_generate_3() {
     mj.apply_sequence(&sequence, true);
 
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'W', b'W', b'R',
-            b'W', b'W', b'R',
-            b'W', b'W', b'R'
+            'W', 'W', 'R',
+            'W', 'W', 'R',
+            'W', 'W', 'R'
         ]
     );
 }
@@ -116,11 +123,12 @@ fn test_generate_3() {
 #[test]
 fn test_generate_4() {
     let mut mj = MarkovJunior::new('.', 3, 3, None);
-    mj.grid = #[rustfmt::skip] vec![
-        b'B', b'B', b'B',
-        b'W', b'W', b'W',
-        b'G', b'G', b'G'
-    ];
+    #[rustfmt::skip]
+    mj.set_grid_symbols(&[
+        'B', 'B', 'B',
+        'W', 'W', 'W',
+        'G', 'G', 'G'
+    ]);
     let sequence = rule_to_sequence(vec![
         Rule {
             patterns: vec![PatternRule::new(
@@ -144,11 +152,11 @@ fn test_generate_4() {
     mj.apply_sequence(&sequence, true);
 
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'W', b'W', b'W',
-            b'W', b'W', b'W',
-            b'R', b'R', b'R'
+            'W', 'W', 'W',
+            'W', 'W', 'W',
+            'R', 'R', 'R'
         ]
     );
 }
@@ -156,11 +164,12 @@ fn test_generate_4() {
 #[test]
 fn test_generate_5() {
     let mut mj = MarkovJunior::new('.', 3, 3, None);
-    mj.grid = #[rustfmt::skip] vec![
-        b'B', b'B', b'B',
-        b'W', b'W', b'W',
-        b'G', b'G', b'G'
-    ];
+    #[rustfmt::skip]
+    mj.set_grid_symbols(&[
+        'B', 'B', 'B',
+        'W', 'W', 'W',
+        'G', 'G', 'G'
+    ]);
 
     let sequence = rule_to_sequence(Rule {
         patterns: vec![PatternRule::new(
@@ -174,11 +183,11 @@ fn test_generate_5() {
     mj.apply_sequence(&sequence, true);
 
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'W', b'W', b'W',
-            b'W', b'W', b'W',
-            b'G', b'G', b'G',
+            'W', 'W', 'W',
+            'W', 'W', 'W',
+            'G', 'G', 'G',
         ]
     );
 }
@@ -186,11 +195,12 @@ fn test_generate_5() {
 #[test]
 fn test_generate_6() {
     let mut mj = MarkovJunior::new('.', 3, 3, None);
-    mj.grid = #[rustfmt::skip] vec![
-        b'B', b'B', b'B',
-        b'B', b'B', b'B',
-        b'B', b'B', b'W',
-    ];
+    #[rustfmt::skip]
+    mj.set_grid_symbols(&[
+        'B', 'B', 'B',
+        'B', 'B', 'B',
+        'B', 'B', 'W',
+    ]);
 
     let sequence = rule_to_sequence(Rule {
         patterns: vec![PatternRule::new(
@@ -204,11 +214,11 @@ fn test_generate_6() {
     mj.apply_sequence(&sequence, true);
 
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'W', b'W', b'W',
-            b'W', b'W', b'W',
-            b'W', b'W', b'W',
+            'W', 'W', 'W',
+            'W', 'W', 'W',
+            'W', 'W', 'W',
         ]
     );
 }
@@ -216,10 +226,11 @@ fn test_generate_6() {
 #[test]
 fn test_generate_7() {
     let mut mj = MarkovJunior::new('.', 2, 2, None);
-    mj.grid = #[rustfmt::skip] vec![
-        b'U', b'B',
-        b'B', b'U',
-    ];
+    #[rustfmt::skip]
+    mj.set_grid_symbols(&[
+        'U', 'B',
+        'B', 'U',
+    ]);
 
     let sequence = rule_to_sequence(Rule {
         patterns: vec![PatternRule::new(
@@ -232,12 +243,426 @@ fn test_generate_7() {
     });
     mj.apply_sequence(&sequence, true);
 
+    // the input fits in several rotations, the first of which turns the output by 90°
     assert_eq!(mj.changes, 1);
     assert_eq!(
-        mj.grid,
+        mj.grid_symbols(),
         #[rustfmt::skip] vec![
-            b'U', b'B',
-            b'U', b'U',
+            'U', 'U',
+            'B', 'U',
+        ]
+    );
+}
+
+fn one_rule(input: &str, output: &str, steps: Option<usize>) -> Rule {
+    Rule {
+        patterns: vec![PatternRule::new(
+            Pattern::new(input),
+            Pattern::new(output),
+            None,
+        )],
+        kind: RuleKind::One,
+        steps,
+    }
+}
+
+#[test]
+fn test_markov_restarts_from_first_child() {
+    let children = || {
+        vec![
+            RuleOrSequence::Rule(one_rule("RW", "RR", None)),
+            RuleOrSequence::Rule(one_rule("B", "W", None)),
         ]
+    };
+
+    // a sequence runs each child to completion, so "RW" never gets another chance
+    let mut mj = MarkovJunior::new_grid("RBB", 3, 1, Some(0));
+    mj.apply_sequence(
+        &Sequence {
+            vec: children(),
+            steps: Some(1),
+        },
+        true,
+    );
+    assert_eq!(mj.grid_symbols(), vec!['R', 'W', 'W']);
+
+    // a markov node goes back to "RW" after every "B" => "W"
+    let mut mj = MarkovJunior::new_grid("RBB", 3, 1, Some(0));
+    mj.apply_sequence(
+        &Sequence {
+            vec: vec![RuleOrSequence::Markov(Sequence {
+                vec: children(),
+                steps: None,
+            })],
+            steps: Some(1),
+        },
+        true,
+    );
+    assert_eq!(mj.grid_symbols(), vec!['R', 'R', 'R']);
+}
+
+#[test]
+fn test_markov_without_matches_is_done() {
+    let mut mj = MarkovJunior::new_grid("BBBB", 2, 2, Some(0));
+    let markov = Sequence {
+        vec: vec![
+            RuleOrSequence::Rule(one_rule("W", "B", None)),
+            RuleOrSequence::Rule(one_rule("R", "B", None)),
+        ],
+        steps: None,
+    };
+
+    assert!(!mj.apply_markov(&markov));
+    assert_eq!(mj.changes, 0);
+}
+
+#[test]
+fn test_markov_rule_steps() {
+    let mut mj = MarkovJunior::new_grid("BBBBBB", 6, 1, Some(0));
+    let markov = Sequence {
+        vec: vec![RuleOrSequence::Rule(one_rule("B", "W", Some(2)))],
+        steps: None,
+    };
+
+    assert!(mj.apply_markov(&markov));
+    assert_eq!(mj.changes, 2);
+    assert_eq!(mj.grid_symbols().iter().filter(|&&c| c == 'W').count(), 2);
+}
+
+#[test]
+fn test_markov_model() {
+    let xml = r#"
+        <markov fill="B" width="5" height="5">
+          <one in="RB" out="RR"/>
+          <one in="B" out="R" steps="1"/>
+        </markov>
+    "#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+    mj.apply_sequence(&sequence, true);
+
+    assert_eq!(mj.grid_symbols(), vec!['R'; 25]);
+    assert_eq!(mj.changes, 25);
+}
+
+#[test]
+fn test_markov_rules_catch_up_with_other_children() {
+    let mut mj = MarkovJunior::new_grid("RBBB", 4, 1, Some(0));
+    let markov = Sequence {
+        vec: vec![
+            RuleOrSequence::Rule(one_rule("WB", "WW", None)),
+            RuleOrSequence::Rule(one_rule("RB", "RW", None)),
+        ],
+        steps: None,
+    };
+
+    // "RB" matched once, which "WB" => "WW" doesn't touch but its own output does
+    assert!(mj.apply_markov(&markov));
+    assert_eq!(mj.grid_symbols(), vec!['R', 'W', 'W', 'W']);
+    assert_eq!(mj.changes, 3);
+}
+
+/// Rule of every input in `inputs`, each written over with `output`.
+fn one_rule_xml(inputs: &[&str], output: &str) -> String {
+    let rules = inputs
+        .iter()
+        .map(|input| format!(r#"<rule in="{input}" out="{output}"/>"#))
+        .collect::<String>();
+    format!("<one>{rules}</one>")
+}
+
+fn match_cell_by_cell(sequence: &mut Sequence) {
+    for node in &mut sequence.vec {
+        match node {
+            RuleOrSequence::Rule(rule) => {
+                for pattern_rule in &mut rule.patterns {
+                    pattern_rule.canonical_key = None;
+                }
+            }
+            RuleOrSequence::Sequence(sequence) | RuleOrSequence::Markov(sequence) => {
+                match_cell_by_cell(sequence)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_markov_rules_match_through_canonical_forms() {
+    let xml = format!(
+        r#"<sequence values="BWRG" fill="B" width="24" height="24">
+  <all in="B" out="W" p="0.5" steps="1"/>
+  <markov>
+    {}
+    {}
+  </markov>
+</sequence>"#,
+        one_rule_xml(
+            &[
+                "WWW/BBB/WBW",
+                "WBW/BWB/BBB",
+                "BBW/WWB/WBW",
+                "WWB/BWW/BWB",
+                "BWB/BBW/WWW",
+                "WBB/BWB/BWW",
+                "BWW/WBW/WBB",
+                "WBW/WWB/BWB",
+            ],
+            "GGG/GGG/GGG",
+        ),
+        one_rule_xml(
+            &[
+                "WWW/BBB", "WBW/BWB", "BBW/WWB", "WWB/BWW", "BWB/BBW", "WBB/BWB", "BWW/WBW",
+                "WBW/WWB",
+            ],
+            "RRR/RRR",
+        ),
+    );
+
+    for seed in 0..4 {
+        let (mut mj, sequence) = parse_xml(&xml, Some(seed)).unwrap();
+        let RuleOrSequence::Markov(markov) = &sequence.vec[1] else {
+            unreachable!()
+        };
+        for rule_or_sequence in &markov.vec {
+            let RuleOrSequence::Rule(rule) = rule_or_sequence else {
+                unreachable!()
+            };
+            assert!(rule.patterns.iter().all(|p| p.canonical_key.is_some()));
+        }
+        mj.apply_sequence(&sequence, true);
+
+        // the same matches are found cell by cell, in the same order
+        let (mut expected, mut sequence) = parse_xml(&xml, Some(seed)).unwrap();
+        match_cell_by_cell(&mut sequence);
+        expected.apply_sequence(&sequence, true);
+
+        assert_eq!(mj.grid_symbols(), expected.grid_symbols(), "seed {seed}");
+        assert_eq!(mj.changes, expected.changes, "seed {seed}");
+        assert!(mj.grid_symbols().contains(&'G'), "seed {seed}");
+        assert!(mj.grid_symbols().contains(&'R'), "seed {seed}");
+    }
+}
+
+/// Patterns of every rule of `sequence`.
+fn sequence_patterns(sequence: &Sequence) -> Vec<&PatternRule> {
+    sequence
+        .vec
+        .iter()
+        .flat_map(|node| match node {
+            RuleOrSequence::Rule(rule) => rule.patterns.iter().collect(),
+            RuleOrSequence::Sequence(sequence) | RuleOrSequence::Markov(sequence) => {
+                sequence_patterns(sequence)
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_builtin_models_match_through_canonical_forms() {
+    let options = ModelOptions {
+        width: Some(40),
+        height: Some(40),
+        ..Default::default()
+    };
+
+    for name in ["River", "FireNoise"] {
+        let model = load_model(name, "does-not-exist", &options).unwrap();
+        for pattern_rule in sequence_patterns(&model.root) {
+            let input = &pattern_rule.input;
+            assert_eq!(
+                pattern_rule.canonical_key.is_some(),
+                input.width * input.height > 1,
+                "{name}: {}",
+                input.line
+            );
+        }
+
+        for seed in 0..3 {
+            let mut mj = model.initial_state(Some(seed));
+            mj.apply_sequence(&model.root, true);
+
+            let mut expected_model = load_model(name, "does-not-exist", &options).unwrap();
+            match_cell_by_cell(&mut expected_model.root);
+            let mut expected = expected_model.initial_state(Some(seed));
+            expected.apply_sequence(&expected_model.root, true);
+
+            assert_eq!(mj.grid_symbols(), expected.grid_symbols(), "{name} {seed}");
+        }
+    }
+}
+
+#[test]
+fn test_deeply_nested_sequences() {
+    let xml = r#"
+        <sequence fill="B" width="4" height="1">
+          <sequence>
+            <one in="B" out="R" steps="1"/>
+            <markov>
+              <sequence steps="1">
+                <one in="RB" out="RG" steps="1"/>
+                <sequence>
+                  <all in="G" out="R"/>
+                </sequence>
+              </sequence>
+            </markov>
+          </sequence>
+          <all in="R" out="W"/>
+        </sequence>
+    "#;
+    let report = Report::default();
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+    mj.apply_sequence_observed(&sequence, true, &mut PrintObserver::new(report.clone()));
+
+    assert_eq!(mj.grid_symbols(), vec!['W'; 4]);
+    // every child of the root is reported once done, with its descendants indented by depth
+    assert_eq!(
+        report.text(),
+        "\
+Sequence steps: None, changes: 7
+  Rule kind: One, steps: Some(1), changes: 1
+  B => R
+  Markov steps: None, changes: 6
+    Sequence steps: Some(1), changes: 6
+      Rule kind: One, steps: Some(1), changes: 3
+      RB => RG
+      Sequence steps: None, changes: 3
+        Rule kind: All, steps: None, changes: 3
+        G => R
+Rule kind: All, steps: None, changes: 4
+R => W
+"
     );
 }
+
+/// Text written by a [`PrintObserver`], readable while the observer still holds it.
+#[derive(Clone, Default)]
+struct Report(Arc<Mutex<Vec<u8>>>);
+
+impl Report {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for Report {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_union_rule() {
+    let xml = r#"
+        <sequence fill="B" width="4" height="4">
+          <union symbol="?" values="BW"/>
+          <all in="B" out="W" steps="1" p="0.5"/>
+          <all in="?" out="R"/>
+        </sequence>
+    "#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+    mj.apply_sequence(&sequence, true);
+
+    assert_eq!(mj.grid_symbols(), vec!['R'; 16]);
+}
+
+#[test]
+fn test_one_rule_skips_matches_without_probability() {
+    let xml = r#"
+        <sequence fill="B" width="4" height="1">
+          <one>
+            <rule in="B" out="W" p="0"/>
+            <rule in="R" out="W"/>
+          </one>
+        </sequence>
+    "#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    assert!(!mj.apply_sequence(&sequence, true));
+    assert_eq!(mj.grid_symbols(), vec!['B'; 4]);
+    assert_eq!(mj.changes, 0);
+}
+
+#[test]
+fn test_invalid_probability() {
+    for p in ["-1", "NaN", "inf"] {
+        let xml = format!(
+            r#"<sequence fill="B" width="4" height="1"><one in="B" out="W" p="{p}"/></sequence>"#
+        );
+        let err = parse_xml(&xml, Some(0)).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(r#"invalid value "{p}" for `p`: must be a finite number that is not negative"#)
+        );
+    }
+}
+
+#[test]
+fn test_all_rule_does_not_overlap() {
+    let xml = r#"
+        <sequence fill="B" width="12" height="1">
+          <all in="BB" out="RW" steps="1" symmetry="()"/>
+        </sequence>
+    "#;
+    let mut results = std::collections::BTreeSet::new();
+
+    for seed in 0..8 {
+        let (mut mj, sequence) = parse_xml(xml, Some(seed)).unwrap();
+        mj.apply_sequence(&sequence, true);
+        let row = mj.grid_symbols().into_iter().collect::<String>();
+
+        // matches are applied in random order, each one only if it still fits
+        assert_eq!(row.replace("RW", ""), "B".repeat(row.matches('B').count()));
+        assert!(!row.contains("BB"));
+        assert_eq!(mj.changes, row.matches("RW").count());
+        results.insert(row);
+    }
+
+    assert!(results.len() > 1);
+}
+
+#[test]
+fn test_parallel_rule_conflicts() {
+    let model = |conflicts: &str| {
+        format!(
+            r#"
+            <sequence fill="B" width="3" height="2">
+              <prl steps="1" conflicts="{conflicts}">
+                <rule in="B" out="R"/>
+                <rule in="B" out="W"/>
+                <rule in="BB" out="R*" symmetry="()"/>
+              </prl>
+            </sequence>
+            "#
+        )
+    };
+
+    // every cell gets written with both R and W, so every match conflicts with another one
+    let (mut mj, sequence) = parse_xml(&model("skip"), Some(0)).unwrap();
+    mj.apply_sequence(&sequence, true);
+    assert_eq!(mj.grid_symbols(), vec!['B'; 6]);
+    assert_eq!(mj.changes, 0);
+
+    for seed in 0..4 {
+        let (mut mj, sequence) = parse_xml(&model("random"), Some(seed)).unwrap();
+        mj.apply_sequence(&sequence, true);
+        assert!(mj.grid_symbols().iter().all(|&c| c == 'R' || c == 'W'));
+        assert!(mj.changes >= 6);
+    }
+}
+
+#[test]
+fn test_parallel_rule_uses_pre_step_grid() {
+    let xml = r#"
+        <sequence fill="B" width="5" height="1">
+          <put x="4" y="0" value="W"/>
+          <prl in="BW" out="WW" steps="1" symmetry="()"/>
+        </sequence>
+    "#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+    mj.apply_sequence(&sequence, true);
+
+    assert_eq!(mj.grid_symbols(), vec!['B', 'B', 'B', 'W', 'W']);
+}
@@ -0,0 +1,111 @@
+use markov_junior::*;
+
+fn pattern_match(x: usize, y: usize, pattern_index: usize) -> PatternMatch {
+    PatternMatch {
+        x,
+        y,
+        z: 0,
+        probability: None,
+        pattern_index,
+        rotation: Transform::Identity,
+    }
+}
+
+fn positions(cache: &MatchCache) -> Vec<(usize, usize, usize)> {
+    let mut positions = cache
+        .matches()
+        .iter()
+        .map(|m| (m.x, m.y, m.pattern_index))
+        .collect::<Vec<_>>();
+    positions.sort();
+    positions
+}
+
+#[test]
+fn test_set_cell_replaces_matches() {
+    let mut cache = MatchCache::new(3, 2, 1, 2);
+    cache.set_cell(0, 0, 0, [pattern_match(0, 0, 0), pattern_match(0, 0, 1)]);
+    cache.set_cell(2, 1, 0, [pattern_match(2, 1, 1)]);
+    cache.set_cell(1, 0, 0, [pattern_match(1, 0, 0)]);
+    assert_eq!(cache.len(), 4);
+
+    // removing the first matches moves the last ones into their place
+    cache.set_cell(0, 0, 0, []);
+    assert_eq!(positions(&cache), vec![(1, 0, 0), (2, 1, 1)]);
+    assert_eq!(cache.cell(0, 0, 0).count(), 0);
+    assert_eq!(cache.cell(1, 0, 0).next(), Some(&pattern_match(1, 0, 0)));
+
+    cache.set_cell(2, 1, 0, [pattern_match(2, 1, 0)]);
+    cache.set_cell(1, 0, 0, []);
+    assert_eq!(positions(&cache), vec![(2, 1, 0)]);
+    assert_eq!(cache.cell(2, 1, 0).next(), Some(&pattern_match(2, 1, 0)));
+
+    cache.set_cell(2, 1, 0, []);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_cells_of_different_layers() {
+    let upper_match = PatternMatch {
+        z: 1,
+        ..pattern_match(1, 1, 0)
+    };
+    let mut cache = MatchCache::new(2, 2, 2, 1);
+    cache.set_cell(1, 1, 0, [pattern_match(1, 1, 0)]);
+    cache.set_cell(1, 1, 1, [upper_match]);
+    assert_eq!(cache.len(), 2);
+
+    cache.set_cell(1, 1, 0, []);
+    assert_eq!(cache.cell(1, 1, 0).count(), 0);
+    assert_eq!(cache.cell(1, 1, 1).map(|m| m.z).next(), Some(1));
+}
+
+#[test]
+fn test_weighted_sampling() {
+    let weighted_match = |x, probability| PatternMatch {
+        probability: Some(probability),
+        ..pattern_match(x, 0, 0)
+    };
+    let mut cache = MatchCache::new(4, 1, 1, 1);
+    cache.set_cell(3, 0, 0, [weighted_match(3, 0.5)]);
+    cache.set_cell(1, 0, 0, [weighted_match(1, 2.0)]);
+    cache.set_cell(2, 0, 0, [weighted_match(2, 0.0)]);
+    assert_eq!(cache.total_weight(), 2.5);
+
+    // matches span their weights in cell order, whatever order they were added in
+    let x_at = |cache: &MatchCache, weight| cache.weighted(weight).map(|m| m.x);
+    assert_eq!(x_at(&cache, 0.0), Some(1));
+    assert_eq!(x_at(&cache, 1.99), Some(1));
+    assert_eq!(x_at(&cache, 2.0), Some(3));
+    assert_eq!(x_at(&cache, 2.49), Some(3));
+
+    // past the matches, the last one with a weight is picked
+    assert_eq!(x_at(&cache, 2.5), Some(3));
+
+    cache.set_cell(1, 0, 0, []);
+    assert_eq!(cache.total_weight(), 0.5);
+    assert_eq!(x_at(&cache, 0.0), Some(3));
+
+    cache.set_cell(3, 0, 0, []);
+    assert_eq!(cache.total_weight(), 0.0);
+    assert_eq!(x_at(&cache, 0.0), None);
+}
+
+#[test]
+fn test_rounded_weights_of_removed_matches() {
+    let weighted_match = |x, probability| PatternMatch {
+        probability: Some(probability),
+        ..pattern_match(x, 0, 0)
+    };
+    let mut cache = MatchCache::new(3, 1, 1, 1);
+    cache.set_cell(0, 0, 0, [weighted_match(0, 0.1)]);
+    cache.set_cell(1, 0, 0, [weighted_match(1, 1e8)]);
+    cache.set_cell(2, 0, 0, [weighted_match(2, 0.0)]);
+    cache.set_cell(1, 0, 0, []);
+    cache.set_cell(0, 0, 0, []);
+
+    // the sums keep what rounding left of the removed weights, but no match has a weight
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.total_weight(), 0.0);
+    assert_eq!(cache.weighted(0.0), None);
+}
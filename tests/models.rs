@@ -0,0 +1,146 @@
+mod common;
+
+use common::load;
+use markov_junior::*;
+use std::path::PathBuf;
+
+fn temp_models_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("markov_junior_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_builtin_models_parse() {
+    for name in ["River", "FireNoise", "Test"] {
+        let model = load_model(name, "does-not-exist", &ModelOptions::default()).unwrap();
+        assert_eq!((model.width, model.height), (150, 150));
+    }
+}
+
+#[test]
+fn test_size_overrides_model_attributes() {
+    let options = ModelOptions {
+        seed: Some(0),
+        width: Some(12),
+        height: Some(7),
+        length: None,
+    };
+    let model = load_model("River", "does-not-exist", &options).unwrap();
+    let mj = model.initial_state(options.seed);
+
+    assert_eq!((model.width, model.height), (12, 7));
+    assert_eq!(mj.grid.len(), 12 * 7);
+}
+
+#[test]
+fn test_size_override_supplies_missing_attributes() {
+    let xml = r#"<sequence fill="B"><one in="B" out="W"/></sequence>"#;
+
+    assert!(matches!(
+        parse_xml(xml, None),
+        Err(ModelError::MissingAttribute { .. })
+    ));
+
+    let options = ModelOptions {
+        width: Some(4),
+        height: Some(4),
+        ..Default::default()
+    };
+    let (mj, _) = parse_xml_with_options(xml, &options).unwrap();
+    assert_eq!((mj.width, mj.height), (4, 4));
+}
+
+#[test]
+fn test_zero_size_override_is_reported_as_the_override() {
+    let xml = r#"<sequence fill="B" width="5" height="5"><one in="B" out="W"/></sequence>"#;
+    let options = ModelOptions {
+        width: Some(0),
+        ..Default::default()
+    };
+
+    let err = parse_model(xml, &options).unwrap_err();
+    assert_eq!(
+        err,
+        ModelError::InvalidOverride {
+            attribute: "width".to_string(),
+            value: "0".to_string(),
+            reason: "must be greater than zero".to_string(),
+        }
+    );
+    assert_eq!(err.position(), None);
+    assert_eq!(
+        err.to_string(),
+        r#"invalid value "0" overriding `width`: must be greater than zero"#
+    );
+}
+
+#[test]
+fn test_models_dir_takes_precedence_over_builtins() {
+    let dir = temp_models_dir("precedence");
+    std::fs::write(
+        dir.join("River.xml"),
+        r#"<sequence fill="W" width="2" height="3"/>"#,
+    )
+    .unwrap();
+
+    let model = load_model("River", &dir, &ModelOptions::default()).unwrap();
+    assert_eq!((model.width, model.height), (2, 3));
+    assert!(model.root.vec.is_empty());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_load_model_file() {
+    let dir = temp_models_dir("file");
+    let path = dir.join("Custom.xml");
+    std::fs::write(&path, r#"<sequence fill="B" width="5" height="5"/>"#).unwrap();
+
+    let model = load_model_file(&path, &ModelOptions::default()).unwrap();
+    assert_eq!((model.width, model.height), (5, 5));
+
+    let model = load_model("Custom", &dir, &ModelOptions::default()).unwrap();
+    assert_eq!((model.width, model.height), (5, 5));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_unknown_model() {
+    assert_eq!(
+        find_model_xml("Nope", "does-not-exist"),
+        Err(ModelError::UnknownModel {
+            name: "Nope".to_string()
+        })
+    );
+    assert!(matches!(
+        load_model_file("does-not-exist/Nope.xml", &ModelOptions::default()),
+        Err(ModelError::Io { .. })
+    ));
+}
+
+#[test]
+fn test_model_is_shared_between_runs() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Model>();
+
+    let model = load("River", 20);
+
+    let handles = (0..4)
+        .map(|seed| {
+            let model = model.clone();
+            std::thread::spawn(move || {
+                let mut run = Run::new(model, Some(seed));
+                run.run();
+                run.mj.grid_symbols()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (seed, handle) in handles.into_iter().enumerate() {
+        let mut run = Run::new(model.clone(), Some(seed as u64));
+        run.run();
+        assert_eq!(handle.join().unwrap(), run.mj.grid_symbols());
+    }
+}
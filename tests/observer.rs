@@ -0,0 +1,91 @@
+use markov_junior::*;
+use std::sync::{Arc, Mutex};
+
+/// Records every callback as a line of text.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Observer for Recorder {
+    fn on_node_start(&mut self, _mj: &MarkovJunior, _node: &RuleOrSequence, path: &[usize]) {
+        self.0.lock().unwrap().push(format!("start {path:?}"));
+    }
+
+    fn on_node_end(
+        &mut self,
+        _mj: &MarkovJunior,
+        _node: &RuleOrSequence,
+        path: &[usize],
+        changes: usize,
+    ) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("end {path:?} {changes}"));
+    }
+
+    fn on_cells_changed(&mut self, _mj: &MarkovJunior, cells: &[(usize, usize, usize)]) {
+        self.0.lock().unwrap().push(format!("cells {cells:?}"));
+    }
+
+    fn on_finish(&mut self, mj: &MarkovJunior) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("finish {}", mj.changes));
+    }
+}
+
+const MODEL: &str = r#"
+    <sequence fill="B" width="3" height="1">
+      <one in="B" out="R" steps="1"/>
+      <markov>
+        <one in="RB" out="RW"/>
+        <all in="W" out="R"/>
+      </markov>
+    </sequence>
+"#;
+
+#[test]
+fn test_observer_callbacks() {
+    let recorder = Recorder::default();
+    let (mut mj, sequence) = parse_xml(MODEL, Some(0)).unwrap();
+    mj.apply_sequence_observed(&sequence, true, &mut recorder.clone());
+
+    let events = recorder.0.lock().unwrap();
+    assert_eq!(events.first().unwrap(), "start [0]");
+    assert!(events[1].starts_with("cells [("));
+    assert_eq!(events[2], "end [0] 1");
+    assert_eq!(events[3], "start [1]");
+    assert_eq!(events.last().unwrap(), &format!("finish {}", mj.changes));
+    assert_eq!(events.iter().filter(|e| e.starts_with("finish")).count(), 1);
+    assert!(events.contains(&"start [1, 1]".to_string()));
+    // the two cells besides the first `R` each turn from `W` to `R` once, wherever it lands
+    let all_changes = events
+        .iter()
+        .filter_map(|e| e.strip_prefix("end [1, 1] "))
+        .map(|changes| changes.parse::<usize>().unwrap())
+        .sum::<usize>();
+    assert_eq!(all_changes, 2);
+
+    let changed_cells = events
+        .iter()
+        .filter(|e| e.starts_with("cells"))
+        .map(|e| e.matches('(').count())
+        .sum::<usize>();
+    assert_eq!(changed_cells, mj.changed_cells);
+}
+
+#[test]
+fn test_interpreter_notifies_like_apply_sequence() {
+    let recorder = Recorder::default();
+    let (mut mj, sequence) = parse_xml(MODEL, Some(0)).unwrap();
+    mj.apply_sequence_observed(&sequence, true, &mut recorder.clone());
+
+    let interpreted = Recorder::default();
+    let model = parse_model(MODEL, &ModelOptions::default()).unwrap();
+    let interpreter =
+        Interpreter::new(Run::new(Arc::new(model), Some(0)).with_observer(interpreted.clone()));
+    assert!(interpreter.count() > 0);
+
+    assert_eq!(*interpreted.0.lock().unwrap(), *recorder.0.lock().unwrap());
+}
@@ -3,9 +3,6 @@ use markov_junior::*;
 
 #[test]
 fn test_patterns_have_no_canonical_form() {
-    let pattern = Pattern::new("WG");
-    assert!(pattern.canonical_form.is_none());
-
     let pattern = Pattern::new("W");
     assert!(pattern.canonical_form.is_none());
 
@@ -16,6 +13,53 @@ fn test_patterns_have_no_canonical_form() {
     assert!(pattern.canonical_form.is_none());
 }
 
+#[test]
+fn test_rectangular_canonical_form() {
+    // the smallest of the rotations that lie flat
+    let pattern = Pattern::new("W/G");
+    let canonical_form = pattern.canonical_form.unwrap();
+    assert_eq!(canonical_form.data, vec!['G', 'W']);
+    assert_eq!((canonical_form.width, canonical_form.height), (2, 1));
+    assert_eq!(canonical_form.rotation, Transform::Rotate90);
+
+    assert_eq!(
+        Pattern::new("WG").canonical_id.unwrap().hash,
+        pattern.canonical_id.unwrap().hash
+    );
+}
+
+#[test]
+fn test_canonical_keys() {
+    assert_eq!(Pattern::new("W").canonical_key, None);
+    assert_eq!(Pattern::new("**/**").canonical_key, None);
+    assert_eq!(
+        Pattern::new("W/G").canonical_key,
+        Some(CanonicalKey { long: 2, short: 1 })
+    );
+    assert_eq!(
+        Pattern::new("WG/BW").canonical_key,
+        Some(CanonicalKey { long: 2, short: 2 })
+    );
+    let pattern = Pattern::new("*G/B*");
+    assert_eq!(
+        pattern.canonical_key,
+        Some(CanonicalKey { long: 2, short: 2 })
+    );
+    assert_eq!(pattern.packed_rotations.len(), pattern.cell_rotations.len());
+
+    // windows too large to pack are matched by their canonical ids, which `*` cells don't have
+    let pattern = Pattern::new("WGBWG/GBWGB/BWGBW/WGBWG");
+    assert!(!pattern.canonical_key.unwrap().is_packed());
+    assert!(pattern.packed_rotations.is_empty());
+    assert_eq!(Pattern::new("*GBWG/GBWGB/BWGBW/WGBWG").canonical_key, None);
+
+    let unions = [('?', vec!['W', 'B'])].into_iter().collect();
+    assert_eq!(
+        Pattern::new_input("?G", &unions).unwrap().canonical_key,
+        None
+    );
+}
+
 #[test]
 fn test_patterns_canonical_form() {
     let pattern = Pattern::new("WW/BB");
@@ -28,33 +72,29 @@ fn test_patterns_canonical_form() {
             'W', 'W',
         ]
     );
-    assert_eq!(canonical_form.rotation, 3);
+    assert_eq!(canonical_form.rotation, Transform::Rotate180);
 }
 
 #[test]
-fn test_rollback_rotation() {
+fn test_inverse_transform() {
     let pattern = Pattern::new("WG/WG");
-    let canonical_form = pattern.canonical_form.unwrap();
-
-    assert_eq!(
-        Pattern::rollback_rotation(
-            &canonical_form.data,
-            pattern.width,
-            pattern.height,
-            canonical_form.rotation
-        ),
-        pattern.data
-    );
-
-    for rotated_data in pattern.rotations {
+    let canonical_form = pattern.canonical_form.clone().unwrap();
+
+    let rollback = |rotated_seq: &RotatedSeq| {
+        rotated_seq.rotation.inverse().apply_to(
+            &rotated_seq.data,
+            rotated_seq.width,
+            rotated_seq.height,
+        )
+    };
+
+    assert_eq!(rollback(&canonical_form).data, pattern.data);
+    for rotated_seq in &pattern.rotations {
+        let rolled_back = rollback(rotated_seq);
+        assert_eq!(rolled_back.data, pattern.data);
         assert_eq!(
-            Pattern::rollback_rotation(
-                &rotated_data.data,
-                pattern.width,
-                pattern.height,
-                rotated_data.rotation
-            ),
-            pattern.data
+            (rolled_back.width, rolled_back.height),
+            (pattern.width, pattern.height)
         );
     }
 }
@@ -90,10 +130,10 @@ fn test_non_square_pattern_2x1() {
     assert_eq!(
         pattern.unique_rotations,
         #[rustfmt::skip] vec![
-            RotatedSeq { data: vec!['A', 'B'], width: 2, height: 1, rotation: 1 },
-            RotatedSeq { data: vec!['A', 'B'], width: 1, height: 2, rotation: 2 },
-            RotatedSeq { data: vec!['B', 'A'], width: 2, height: 1, rotation: 3 },
-            RotatedSeq { data: vec!['B', 'A'], width: 1, height: 2, rotation: 4 }
+            RotatedSeq { data: vec!['A', 'B'], width: 2, height: 1, depth: 1, rotation: Transform::Identity },
+            RotatedSeq { data: vec!['A', 'B'], width: 1, height: 2, depth: 1, rotation: Transform::Rotate90 },
+            RotatedSeq { data: vec!['B', 'A'], width: 2, height: 1, depth: 1, rotation: Transform::Rotate180 },
+            RotatedSeq { data: vec!['B', 'A'], width: 1, height: 2, depth: 1, rotation: Transform::Rotate270 }
         ]
     );
 }
@@ -162,39 +202,113 @@ fn test_pattern_with_anything_input() {
 #[test]
 fn test_rotations() {
     let data = vec!['A', 'B', 'C', 'D'];
-    let rotated = Pattern::rotate_90(&data, 2, 2);
-    assert_eq!(rotated, vec!['C', 'A', 'D', 'B']);
+    let rotated = Transform::Rotate90.apply_to(&data, 2, 2);
+    assert_eq!(rotated.data, vec!['C', 'A', 'D', 'B']);
 
     let data = vec!['1', '2', '3', '4', '5', '6'];
-    let rotated = Pattern::rotate_90(&data, 3, 2);
-    assert_eq!(rotated, vec!['4', '1', '5', '2', '6', '3']);
+    let rotated = Transform::Rotate90.apply_to(&data, 3, 2);
+    assert_eq!(rotated.data, vec!['4', '1', '5', '2', '6', '3']);
+    assert_eq!((rotated.width, rotated.height), (2, 3));
 
     let data = vec!['A', 'B', 'C', 'D'];
-    let rotated = Pattern::rotate_180(&data);
-    assert_eq!(rotated, vec!['D', 'C', 'B', 'A']);
+    let rotated = Transform::Rotate180.apply_to(&data, 2, 2);
+    assert_eq!(rotated.data, vec!['D', 'C', 'B', 'A']);
 
     let data = vec!['1', '2', '3', '4', '5', '6'];
-    let rotated = Pattern::rotate_180(&data);
-    assert_eq!(rotated, vec!['6', '5', '4', '3', '2', '1']);
+    let rotated = Transform::Rotate180.apply_to(&data, 3, 2);
+    assert_eq!(rotated.data, vec!['6', '5', '4', '3', '2', '1']);
 
     let data = vec!['A', 'B', 'C', 'D'];
-    let rotated = Pattern::rotate_270(&data, 2, 2);
-    assert_eq!(rotated, vec!['B', 'D', 'A', 'C']);
+    let rotated = Transform::Rotate270.apply_to(&data, 2, 2);
+    assert_eq!(rotated.data, vec!['B', 'D', 'A', 'C']);
 
     let data = vec!['1', '2', '3', '4', '5', '6'];
-    let rotated = Pattern::rotate_270(&data, 3, 2);
-    assert_eq!(rotated, vec!['3', '6', '2', '5', '1', '4']);
+    let rotated = Transform::Rotate270.apply_to(&data, 3, 2);
+    assert_eq!(rotated.data, vec!['3', '6', '2', '5', '1', '4']);
 }
 
 #[test]
 fn test_mirror() {
     let data = vec!['A', 'B', 'C', 'D'];
-    let mirrored = Pattern::mirror(&data, 2);
-    assert_eq!(mirrored, vec!['B', 'A', 'D', 'C']);
+    let mirrored = Transform::Mirror.apply_to(&data, 2, 2);
+    assert_eq!(mirrored.data, vec!['B', 'A', 'D', 'C']);
 
     let data = vec!['1', '2', '3', '4', '5', '6'];
-    let mirrored = Pattern::mirror(&data, 3);
-    assert_eq!(mirrored, vec!['3', '2', '1', '6', '5', '4']);
+    let mirrored = Transform::Mirror.apply_to(&data, 3, 2);
+    assert_eq!(mirrored.data, vec!['3', '2', '1', '6', '5', '4']);
+
+    // mirroring happens before rotating
+    let rotated = Transform::MirrorRotate90.apply_to(&data, 3, 2);
+    assert_eq!(
+        rotated.data,
+        Transform::Rotate90.apply_to(&mirrored.data, 3, 2).data
+    );
+}
+
+#[test]
+fn test_transform_source() {
+    let data = ('A'..='F').collect::<Vec<_>>();
+
+    for rotated_seq in Pattern::compute_rotations(&data, 3, 2) {
+        for (i, &symbol) in rotated_seq.data.iter().enumerate() {
+            let (rx, ry) = (i % rotated_seq.width, i / rotated_seq.width);
+            let (x, y) = rotated_seq.rotation.source(3, 2, rx, ry);
+            assert_eq!(data[y * 3 + x], symbol, "{:?}", rotated_seq.rotation);
+        }
+    }
+}
+
+#[test]
+fn test_transform_composition() {
+    let data = ('A'..='F').collect::<Vec<_>>();
+
+    for first in Transform::ALL {
+        let once = first.apply_to(&data, 3, 2);
+        assert_eq!(first.then(first.inverse()), Transform::Identity);
+        assert_eq!(first.inverse().then(first), Transform::Identity);
+
+        for second in Transform::ALL {
+            let twice = second.apply_to(&once.data, once.width, once.height);
+            let composed = first.then(second).apply_to(&data, 3, 2);
+            assert_eq!(
+                (composed.data, composed.width, composed.height),
+                (twice.data, twice.width, twice.height),
+                "{first:?} then {second:?}"
+            );
+        }
+    }
+
+    assert_eq!(
+        Transform::Rotate90.then(Transform::Rotate270),
+        Transform::Identity
+    );
+    assert_eq!(
+        Transform::Mirror.then(Transform::Rotate90),
+        Transform::MirrorRotate90
+    );
+    assert_eq!(
+        Transform::Rotate90.then(Transform::Mirror),
+        Transform::MirrorRotate270
+    );
+}
+
+#[test]
+fn test_canonical_id() {
+    let pattern = Pattern::new("WW/BB");
+    let rotated = Pattern::new("BW/BW");
+
+    let canonical_id = pattern.canonical_id.unwrap();
+    assert_eq!(
+        canonical_id,
+        CanonicalId::of(&pattern.canonical_form.unwrap())
+    );
+    assert_eq!(canonical_id.rotation, Transform::Rotate180);
+    assert_eq!(canonical_id.hash, rotated.canonical_id.unwrap().hash);
+    assert_ne!(
+        canonical_id.hash,
+        Pattern::new("WB/BB").canonical_id.unwrap().hash
+    );
+    assert!(Pattern::new("W*/BB").canonical_id.is_none());
 }
 
 #[test]
@@ -204,28 +318,28 @@ fn test_compute_canonical_form() {
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 1);
+    assert_eq!(canonical_form.rotation, Transform::Identity);
 
     let data = vec!['B', 'D', 'A', 'C'];
     let canonical_form = Pattern::compute_canonical_form_and_rotations(&data, 2, 2, false)
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 2);
+    assert_eq!(canonical_form.rotation, Transform::Rotate90);
 
     let data = vec!['D', 'C', 'B', 'A'];
     let canonical_form = Pattern::compute_canonical_form_and_rotations(&data, 2, 2, false)
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 3);
+    assert_eq!(canonical_form.rotation, Transform::Rotate180);
 
     let data = vec!['C', 'A', 'D', 'B'];
     let canonical_form = Pattern::compute_canonical_form_and_rotations(&data, 2, 2, false)
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 4);
+    assert_eq!(canonical_form.rotation, Transform::Rotate270);
 }
 
 #[test]
@@ -235,14 +349,14 @@ fn test_compute_canonical_form_with_mirror() {
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, -1);
+    assert_eq!(canonical_form.rotation, Transform::Mirror);
 
     let data = vec!['C', 'D', 'A', 'B'];
     let canonical_form = Pattern::compute_canonical_form_and_rotations(&data, 2, 2, false)
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, -3);
+    assert_eq!(canonical_form.rotation, Transform::MirrorRotate180);
 }
 
 #[test]
@@ -252,7 +366,7 @@ fn test_compute_canonical_form_2x2_no_rotation() {
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 1);
+    assert_eq!(canonical_form.rotation, Transform::Identity);
 }
 
 #[test]
@@ -262,7 +376,7 @@ fn test_compute_canonical_form_2x2_90_rotation() {
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 2);
+    assert_eq!(canonical_form.rotation, Transform::Rotate90);
 }
 
 #[test]
@@ -272,7 +386,7 @@ fn test_compute_canonical_form_2x2_180_rotation() {
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 3);
+    assert_eq!(canonical_form.rotation, Transform::Rotate180);
 }
 
 #[test]
@@ -282,7 +396,7 @@ fn test_compute_canonical_form_2x2_270_rotation() {
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'B', 'C', 'D']);
-    assert_eq!(canonical_form.rotation, 4);
+    assert_eq!(canonical_form.rotation, Transform::Rotate270);
 }
 
 #[test]
@@ -295,7 +409,7 @@ fn test_compute_canonical_form_3x3() {
         canonical_form.data,
         vec!['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I']
     );
-    assert_eq!(canonical_form.rotation, 2);
+    assert_eq!(canonical_form.rotation, Transform::Rotate90);
 }
 
 #[test]
@@ -305,5 +419,284 @@ fn test_compute_canonical_form_with_repeated_characters() {
         .0
         .unwrap();
     assert_eq!(canonical_form.data, vec!['A', 'A', 'B', 'B']);
-    assert_eq!(canonical_form.rotation, 1);
+    assert_eq!(canonical_form.rotation, Transform::Identity);
+}
+
+#[test]
+fn test_symmetry_restricts_unique_rotations() {
+    let pattern = Pattern::new("AB").with_symmetry("()".parse().unwrap());
+    assert_eq!(pattern.rotations.len(), 8);
+    assert_eq!(pattern.unique_rotations.len(), 1);
+    assert_eq!(pattern.unique_rotations[0].rotation, Transform::Identity);
+
+    let pattern = Pattern::new("AB").with_symmetry("(x)".parse().unwrap());
+    assert_eq!(
+        pattern
+            .unique_rotations
+            .iter()
+            .map(|r| r.rotation)
+            .collect::<Vec<_>>(),
+        vec![Transform::Identity, Transform::Mirror]
+    );
+
+    let pattern = Pattern::new("AB").with_symmetry("(y)".parse().unwrap());
+    assert_eq!(
+        pattern
+            .unique_rotations
+            .iter()
+            .map(|r| r.rotation)
+            .collect::<Vec<_>>(),
+        vec![Transform::Identity]
+    );
+
+    let pattern = Pattern::new("AB/CD").with_symmetry("(xy+)".parse().unwrap());
+    assert_eq!(
+        pattern
+            .unique_rotations
+            .iter()
+            .map(|r| r.rotation)
+            .collect::<Vec<_>>(),
+        vec![
+            Transform::Identity,
+            Transform::Rotate90,
+            Transform::Rotate180,
+            Transform::Rotate270
+        ]
+    );
+}
+
+#[test]
+fn test_allowed_rotation() {
+    // "AA/BB" mirrored along x is itself, so mirroring it is an alias of leaving it be
+    let pattern = Pattern::new("AA/BB").with_symmetry("()".parse().unwrap());
+    assert_eq!(
+        pattern.allowed_rotation(Transform::Identity),
+        Some(Transform::Identity)
+    );
+    assert_eq!(
+        pattern.allowed_rotation(Transform::Mirror),
+        Some(Transform::Identity)
+    );
+    assert_eq!(pattern.allowed_rotation(Transform::Rotate180), None);
+}
+
+#[test]
+fn test_parse_symmetry() {
+    assert_eq!("(xy)".parse::<Symmetry>(), Ok(Symmetry::ALL));
+    assert_eq!(
+        "(x)(y)".parse::<Symmetry>(),
+        Ok(Symmetry::from_transforms(&[
+            Transform::Identity,
+            Transform::Mirror,
+            Transform::Rotate180,
+            Transform::MirrorRotate180,
+        ]))
+    );
+    assert_eq!("(xyz)".parse::<Symmetry>(), Ok(Symmetry::CUBE));
+    assert_eq!(
+        "(z)".parse::<Symmetry>(),
+        Ok(Symmetry::from_tilted_transforms([
+            (Tilt::ZUp, Transform::Identity),
+            (Tilt::ZDown, Transform::MirrorRotate180),
+        ]))
+    );
+    assert!("(w)".parse::<Symmetry>().is_err());
+}
+
+/// Number of distinct inputs the copies of a rule match on 3D grids.
+fn cube_orientations(input: &str, symmetry: &str) -> usize {
+    let pattern = Pattern::new(input);
+    PatternRule::new(pattern.clone(), pattern, None)
+        .with_cube_symmetry(symmetry.parse().unwrap())
+        .iter()
+        .map(|rule| rule.input.cell_rotations.len())
+        .sum()
+}
+
+#[test]
+fn test_cube_symmetry() {
+    // a pair of cells can point along either way of each axis
+    assert_eq!(cube_orientations("AB", "(xyz)"), 6);
+    assert_eq!(cube_orientations("AB", "(xyz+)"), 6);
+    assert_eq!(cube_orientations("AB", "(xy)"), 4);
+    assert_eq!(cube_orientations("AB", "(z)"), 1);
+    assert_eq!(cube_orientations("A B", "(z)"), 2);
+    assert_eq!(cube_orientations("AA", "(xyz)"), 3);
+
+    // a corner of three different cells has 24 rotations and as many mirror images
+    assert_eq!(cube_orientations("AB/C* D*/**", "(xyz+)"), 24);
+    assert_eq!(cube_orientations("AB/C* D*/**", "(xyz)"), 48);
+}
+
+#[test]
+fn test_tilt() {
+    let data = ['A', 'B', 'C', 'D', 'E', 'F'];
+
+    // a row of three cells, two layers high
+    let (tilted, size) = Tilt::ZUp.apply_to(&data, [3, 1, 2]);
+    assert_eq!((tilted, size), (data.to_vec(), [3, 1, 2]));
+    let (tilted, size) = Tilt::ZDown.apply_to(&data, [3, 1, 2]);
+    assert_eq!((tilted, size), ("DEFABC".chars().collect(), [3, 1, 2]));
+    let (tilted, size) = Tilt::XUp.apply_to(&data, [3, 1, 2]);
+    assert_eq!((tilted, size), ("DAEBFC".chars().collect(), [2, 1, 3]));
+    let (tilted, size) = Tilt::YUp.apply_to(&data, [3, 1, 2]);
+    assert_eq!((tilted, size), ("DEFABC".chars().collect(), [3, 2, 1]));
+}
+
+#[test]
+fn test_pattern_with_layers() {
+    // the first layer written is the top one
+    let pattern = Pattern::new("EF/GH AB/CD");
+    assert_eq!((pattern.width, pattern.height, pattern.depth), (2, 2, 2));
+    assert_eq!(pattern.data, "ABCDEFGH".chars().collect::<Vec<_>>());
+    assert_eq!(pattern.canonical_key, None);
+
+    // every layer turns in place
+    let rotated = pattern.rotated(Transform::Rotate90);
+    assert_eq!(rotated.data, "CADBGEHF".chars().collect::<Vec<_>>());
+    assert_eq!(rotated.depth, 2);
+
+    let tilted = pattern.tilted(Tilt::XUp);
+    assert_eq!((tilted.width, tilted.height, tilted.depth), (2, 2, 2));
+    assert_eq!(tilted.data, "EAGCFBHD".chars().collect::<Vec<_>>());
+
+    let err = Pattern::new_output("AB/CD EF").unwrap_err();
+    assert_eq!(err, "layer 2 has 1 rows but the first layer has 2");
+}
+
+#[test]
+fn test_pattern_with_unions() {
+    let unions = [('?', vec!['W', 'B']), ('%', vec!['R'])]
+        .into_iter()
+        .collect();
+
+    let pattern = Pattern::new_input("?G/GG", &unions).unwrap();
+    assert!(pattern.canonical_form.is_none());
+    assert_eq!(pattern.cells[0], PatternCell::OneOf(vec!['B', 'W']));
+    assert_eq!(pattern.data, vec![ANYTHING, 'G', 'G', 'G']);
+    assert!(pattern.cells[0].matches('B'));
+    assert!(pattern.cells[0].matches('W'));
+    assert!(!pattern.cells[0].matches('R'));
+
+    // a single-valued union is just a symbol
+    let pattern = Pattern::new_input("%G/GG", &unions).unwrap();
+    assert!(pattern.canonical_form.is_some());
+    assert_eq!(pattern.data, vec!['R', 'G', 'G', 'G']);
+}
+
+#[test]
+fn test_pattern_with_character_classes() {
+    let unions = [('?', vec!['B', 'W'])].into_iter().collect();
+    let pattern = Pattern::new_input("[RG]!B/[^?R]*", &unions).unwrap();
+
+    assert_eq!(pattern.width, 2);
+    assert_eq!(pattern.height, 2);
+    assert_eq!(
+        pattern.cells,
+        vec![
+            PatternCell::OneOf(vec!['G', 'R']),
+            PatternCell::NoneOf(vec!['B']),
+            PatternCell::NoneOf(vec!['B', 'R', 'W']),
+            PatternCell::Anything,
+        ]
+    );
+    assert!(pattern.has_wildcards);
+    assert!(pattern.canonical_form.is_none());
+    assert!(pattern.cells[1].matches('W'));
+    assert!(!pattern.cells[1].matches('B'));
+    assert!(pattern.cells[2].matches('G'));
+    assert!(!pattern.cells[2].matches('W'));
+
+    let pattern = Pattern::new_input("[B]", &unions).unwrap();
+    assert_eq!(pattern.cells, vec![PatternCell::Symbol('B')]);
+}
+
+#[test]
+fn test_character_class_rotations() {
+    let pattern = Pattern::new_input("[RG]!B", &Default::default()).unwrap();
+
+    assert_eq!(pattern.unique_rotations.len(), 4);
+    assert_eq!(
+        pattern.cell_rotations[1],
+        RotatedSeq {
+            data: vec![
+                PatternCell::OneOf(vec!['G', 'R']),
+                PatternCell::NoneOf(vec!['B'])
+            ],
+            width: 1,
+            height: 2,
+            depth: 1,
+            rotation: Transform::Rotate90,
+        }
+    );
+
+    // the two cells differ even though both are stored as ANYTHING in `data`
+    let pattern = Pattern::new_input("[RG]!B/!B[RG]", &Default::default()).unwrap();
+    assert_eq!(pattern.unique_rotations.len(), 2);
+    assert_eq!(pattern.data, vec![ANYTHING, ANYTHING, ANYTHING, ANYTHING]);
+}
+
+#[test]
+fn test_invalid_input_patterns() {
+    let unions = Default::default();
+
+    assert!(Pattern::new_input("[RG", &unions).is_err());
+    assert!(Pattern::new_input("[]B", &unions).is_err());
+    assert!(Pattern::new_input("[^]B", &unions).is_err());
+    assert!(Pattern::new_input("B!", &unions).is_err());
+    assert!(Pattern::new_input("B]", &unions).is_err());
+    assert!(Pattern::new_input("[R*]", &unions).is_err());
+    assert!(Pattern::new_input("[RG]B/B", &unions).is_err());
+}
+
+#[test]
+fn test_pattern_with_border() {
+    let with_border = |line| Pattern::new_input_with_border(line, &Default::default(), BORDER);
+    let pattern = with_border("#W/*B").unwrap();
+
+    assert_eq!(
+        pattern.cells,
+        vec![
+            PatternCell::Border,
+            PatternCell::Symbol('W'),
+            PatternCell::Anything,
+            PatternCell::Symbol('B'),
+        ]
+    );
+    assert_eq!(pattern.data, vec![NOTHING, 'W', ANYTHING, 'B']);
+    assert!(pattern.has_border);
+    assert_eq!(
+        pattern.canonical_key,
+        Some(CanonicalKey { long: 2, short: 2 })
+    );
+    assert!(pattern.cells[0].matches(NOTHING));
+    assert!(!pattern.cells[0].matches('W'));
+    // every other cell has to be on the grid
+    assert!(!pattern.cells[2].matches(NOTHING));
+
+    let pattern = Pattern::new_input_with_border("_W", &Default::default(), '_').unwrap();
+    assert_eq!(pattern.cells[0], PatternCell::Border);
+    assert!(pattern.canonical_form.is_some());
+    let pattern = with_border("#W").unwrap();
+    assert!(!pattern.has_wildcards);
+    assert_eq!(
+        pattern.canonical_key,
+        Some(CanonicalKey { long: 2, short: 1 })
+    );
+
+    assert!(with_border("[#W]").is_err());
+    assert!(with_border("!#W").is_err());
+    assert!(with_border("##/##").is_err());
+
+    // without a border, `#` is a symbol like any other
+    let pattern = Pattern::new_input("#W", &Default::default()).unwrap();
+    assert_eq!(pattern.cells[0], PatternCell::Symbol(BORDER));
+    assert!(!pattern.has_border);
+}
+
+#[test]
+fn test_output_pattern_rejects_classes() {
+    assert!(Pattern::new_output("R*").is_ok());
+    assert!(Pattern::new_output("[RG]").is_err());
+    assert!(Pattern::new_output("!B").is_err());
 }
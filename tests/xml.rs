@@ -0,0 +1,679 @@
+use markov_junior::*;
+
+fn parse_error(xml: &str) -> ModelError {
+    match parse_xml(xml, Some(0)) {
+        Ok(_) => panic!("expected model to be rejected"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn test_parse_valid_model() {
+    let (mj, sequence) = parse_xml(
+        r#"<sequence fill="B" width="3" height="2"><one in="B" out="W"/></sequence>"#,
+        Some(0),
+    )
+    .unwrap();
+
+    assert_eq!(mj.width, 3);
+    assert_eq!(mj.height, 2);
+    assert_eq!(sequence.vec.len(), 1);
+}
+
+#[test]
+fn test_malformed_xml() {
+    let err = parse_error("<sequence fill=\"B\" width=\"3\" height=\"3\">\n  </one>");
+
+    assert!(matches!(err, ModelError::Xml { .. }));
+    assert_eq!(err.position().unwrap().row, 2);
+    assert_eq!(err.path(), None);
+}
+
+#[test]
+fn test_missing_root_attribute() {
+    let err = parse_error(r#"<sequence fill="B" width="3"><one in="B" out="W"/></sequence>"#);
+
+    assert_eq!(
+        err,
+        ModelError::MissingAttribute {
+            position: TextPos::new(1, 1),
+            path: "/sequence".to_string(),
+            attribute: "height".to_string(),
+        }
+    );
+}
+
+#[test]
+fn test_invalid_steps() {
+    let xml = r#"<sequence fill="B" width="3" height="3">
+  <one in="B" out="W"/>
+  <all in="B" out="W" steps="many"/>
+</sequence>"#;
+    let err = parse_error(xml);
+
+    assert!(
+        matches!(err, ModelError::InvalidAttribute { ref attribute, .. } if attribute == "steps")
+    );
+    assert_eq!(err.position(), Some(TextPos::new(3, 23)));
+    assert_eq!(err.path(), Some("/sequence/all[1]"));
+}
+
+#[test]
+fn test_unknown_node() {
+    let xml = r#"<sequence fill="B" width="3" height="3">
+  <one in="B" out="W"/>
+  <one>
+    <rule in="B" out="W"/>
+    <rul in="W" out="B"/>
+  </one>
+</sequence>"#;
+    let err = parse_error(xml);
+
+    assert!(matches!(err, ModelError::UnknownNode { ref tag, .. } if tag == "rul"));
+    assert_eq!(err.position(), Some(TextPos::new(5, 5)));
+    assert_eq!(err.path(), Some("/sequence/one[2]/rul[1]"));
+}
+
+#[test]
+fn test_rule_without_output() {
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3"><one><rule in="B"/></one></sequence>"#,
+    );
+
+    assert!(
+        matches!(err, ModelError::MissingAttribute { ref attribute, .. } if attribute == "out")
+    );
+    assert_eq!(err.path(), Some("/sequence/one[1]/rule[1]"));
+}
+
+#[test]
+fn test_mismatched_pattern_sizes() {
+    let err =
+        parse_error(r#"<sequence fill="B" width="3" height="3"><one in="BW" out="B"/></sequence>"#);
+
+    assert!(
+        matches!(err, ModelError::InvalidAttribute { ref attribute, .. } if attribute == "out")
+    );
+}
+
+#[test]
+fn test_diagnostic_points_at_attribute() {
+    let xml = "<sequence fill=\"B\" width=\"x\" height=\"3\"/>";
+    let err = parse_error(xml);
+
+    assert_eq!(
+        err.diagnostic(xml, "Model.xml"),
+        [
+            "error: invalid value \"x\" for `width`: invalid digit found in string",
+            " --> Model.xml:1:20",
+            "  |",
+            "1 | <sequence fill=\"B\" width=\"x\" height=\"3\"/>",
+            "  |                    ^",
+            "  = node: /sequence",
+            "",
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn test_symmetry_is_inherited() {
+    let xml = r#"<sequence fill="B" width="3" height="3" symmetry="(x)">
+  <one in="RB" out="RR"/>
+  <one symmetry="()">
+    <rule in="RB" out="RR"/>
+    <rule in="RB" out="RR" symmetry="(xy)"/>
+  </one>
+</sequence>"#;
+    let (_, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    let symmetries = sequence
+        .vec
+        .iter()
+        .flat_map(|rule_or_sequence| match rule_or_sequence {
+            RuleOrSequence::Rule(rule) => rule.patterns.iter().map(|p| p.input.symmetry),
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        symmetries,
+        vec!["(x)".parse().unwrap(), "()".parse().unwrap(), Symmetry::ALL]
+    );
+}
+
+#[test]
+fn test_invalid_symmetry() {
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3"><one in="B" out="W" symmetry="(q)"/></sequence>"#,
+    );
+
+    assert!(
+        matches!(err, ModelError::InvalidAttribute { ref attribute, .. } if attribute == "symmetry")
+    );
+}
+
+#[test]
+fn test_unions_are_scoped() {
+    let xml = r#"<sequence fill="B" width="3" height="3">
+  <union symbol="?" values="BW"/>
+  <one in="?R" out="RR"/>
+  <sequence>
+    <union symbol="?" values="G"/>
+    <one in="?R" out="RR"/>
+  </sequence>
+  <one>
+    <union symbol="%" values="RG"/>
+    <rule in="%?" out="RR"/>
+  </one>
+</sequence>"#;
+    let (_, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    let RuleOrSequence::Rule(rule) = &sequence.vec[0] else {
+        unreachable!()
+    };
+    assert_eq!(
+        rule.patterns[0].input.cells[0],
+        PatternCell::OneOf(vec!['B', 'W'])
+    );
+
+    let RuleOrSequence::Sequence(nested) = &sequence.vec[1] else {
+        unreachable!()
+    };
+    let RuleOrSequence::Rule(rule) = &nested.vec[0] else {
+        unreachable!()
+    };
+    assert_eq!(rule.patterns[0].input.cells[0], PatternCell::Symbol('G'));
+
+    let RuleOrSequence::Rule(rule) = &sequence.vec[2] else {
+        unreachable!()
+    };
+    assert_eq!(
+        rule.patterns[0].input.cells,
+        vec![
+            PatternCell::OneOf(vec!['G', 'R']),
+            PatternCell::OneOf(vec!['B', 'W'])
+        ]
+    );
+}
+
+#[test]
+fn test_union_symbol_is_not_reserved() {
+    for symbol in ['*', '/', ' ', '[', ']', '^', '!', NOTHING] {
+        let err = parse_error(&format!(
+            r#"<sequence fill="B" width="3" height="3">
+  <union symbol="{symbol}" values="BW"/>
+</sequence>"#
+        ));
+
+        let ModelError::InvalidAttribute {
+            attribute, reason, ..
+        } = err
+        else {
+            panic!("{symbol}: {err:?}");
+        };
+        assert_eq!(attribute, "symbol");
+        assert_eq!(reason, format!("'{symbol}' is reserved"));
+    }
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3">
+  <union symbol="?" values="B W"/>
+</sequence>"#,
+    );
+    assert!(
+        matches!(
+            &err,
+            ModelError::InvalidAttribute { attribute, reason, .. }
+                if attribute == "values" && reason == "' ' is reserved"
+        ),
+        "{err:?}"
+    );
+}
+
+#[test]
+fn test_union_in_output_is_rejected() {
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3">
+  <union symbol="?" values="BW"/>
+  <one in="?" out="?"/>
+</sequence>"#,
+    );
+
+    assert!(
+        matches!(err, ModelError::InvalidAttribute { ref attribute, .. } if attribute == "out")
+    );
+}
+
+#[test]
+fn test_union_out_of_scope_is_literal() {
+    let (_, sequence) = parse_xml(
+        r#"<sequence fill="B" width="3" height="3">
+  <sequence>
+    <union symbol="?" values="BW"/>
+  </sequence>
+  <one in="?" out="W"/>
+</sequence>"#,
+        Some(0),
+    )
+    .unwrap();
+
+    let RuleOrSequence::Rule(rule) = &sequence.vec[1] else {
+        unreachable!()
+    };
+    assert_eq!(rule.patterns[0].input.cells, vec![PatternCell::Symbol('?')]);
+}
+
+#[test]
+fn test_character_class_in_output_is_rejected() {
+    let xml = r#"<sequence fill="B" width="3" height="3">
+  <one in="[BW]" out="[RG]"/>
+</sequence>"#;
+    let err = parse_error(xml);
+
+    assert!(
+        matches!(err, ModelError::InvalidAttribute { ref attribute, .. } if attribute == "out")
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"[RG]\" for `out`: '[': character classes and negation can only be used in `in`"
+    );
+}
+
+#[test]
+fn test_values_alphabet() {
+    let xml = r#"<sequence values="BWR" fill="B" width="3" height="3">
+  <one in="B" out="W"/>
+</sequence>"#;
+    let (mj, _) = parse_xml(xml, Some(0)).unwrap();
+    assert_eq!(mj.alphabet.symbols(), &['B', 'W', 'R']);
+
+    // without `values`, the alphabet is made of the symbols the model uses
+    let xml = r#"<sequence fill="B" width="3" height="3">
+  <one in="[^W]*" out="RW"/>
+  <one in="!U" out="B"/>
+</sequence>"#;
+    let (mj, _) = parse_xml(xml, Some(0)).unwrap();
+    assert_eq!(mj.alphabet.symbols(), &['B', 'W', 'R', 'U']);
+}
+
+#[test]
+fn test_unknown_symbol_is_rejected() {
+    let xml = r#"<sequence values="BWR" fill="B" width="3" height="3">
+  <one in="Rb" out="RR"/>
+</sequence>"#;
+    let err = parse_error(xml);
+
+    assert_eq!(
+        err,
+        ModelError::UnknownSymbol {
+            position: TextPos::new(2, 8),
+            path: "/sequence/one[1]".to_string(),
+            attribute: "in".to_string(),
+            symbol: 'b',
+            suggestion: Some('B'),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "unknown symbol 'b' in `in`, did you mean 'B'?"
+    );
+
+    for xml in [
+        r#"<sequence values="BWR" fill="B" width="3" height="3"><one in="B" out="[W]"/></sequence>"#,
+        r#"<sequence values="BWR" fill="B" width="3" height="3"><one in="[^BG]" out="W"/></sequence>"#,
+        r#"<sequence values="BWR" fill="B" width="3" height="3"><one in="B" out="U"/></sequence>"#,
+        r#"<sequence values="BWR" fill="G" width="3" height="3"/>"#,
+        r#"<sequence values="BWR" fill="B" width="3" height="3"><union symbol="?" values="BG"/></sequence>"#,
+    ] {
+        let err = parse_error(xml);
+        assert!(
+            matches!(
+                err,
+                ModelError::UnknownSymbol { .. } | ModelError::InvalidAttribute { .. }
+            ),
+            "{err:?}"
+        );
+    }
+}
+
+#[test]
+fn test_invalid_values() {
+    for (values, reason) in [
+        ("", "expected at least one symbol"),
+        ("BWB", "'B' is listed more than once"),
+        ("B*W", "'*' is reserved"),
+        ("B W", "' ' is reserved"),
+        ("B^W", "'^' is reserved"),
+        ("B❌", "'❌' is reserved"),
+    ] {
+        let xml = format!(r#"<sequence values="{values}" fill="B" width="3" height="3"/>"#);
+        let ModelError::InvalidAttribute {
+            attribute,
+            reason: actual_reason,
+            ..
+        } = parse_error(&xml)
+        else {
+            panic!("expected `values` to be rejected");
+        };
+        assert_eq!(attribute, "values");
+        assert_eq!(actual_reason, reason);
+    }
+
+    let err = parse_error(
+        r#"<sequence values="BWR" fill="B" width="3" height="3"><union symbol="W" values="BR"/></sequence>"#,
+    );
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "symbol"));
+}
+
+#[test]
+fn test_origin_and_put() {
+    let xml = r#"<sequence values="BWR" fill="B" width="5" height="4" origin="True">
+  <put x="0" y="3" value="R"/>
+  <put x="4" y="0" value="W"/>
+</sequence>"#;
+    let (mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    assert!(sequence.vec.is_empty());
+    assert_eq!(
+        mj.grid_symbols().into_iter().collect::<String>(),
+        "BBBBW\
+         BBBBB\
+         BBWBB\
+         RBBBB"
+    );
+}
+
+#[test]
+fn test_invalid_origin_and_put() {
+    let err = parse_error(r#"<sequence fill="B" width="5" height="5" origin="yes"/>"#);
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "origin"));
+
+    let err = parse_error(r#"<sequence values="B" fill="B" width="5" height="5" origin="True"/>"#);
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "origin"));
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="5" height="5" origin="True"><one in="B" out="W"/></sequence>"#,
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"True\" for `origin`: the root needs `values`, whose second symbol is put at the origin"
+    );
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="5" height="5"><put x="5" y="0" value="W"/></sequence>"#,
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"5\" for `x`: outside the 5x5 grid"
+    );
+
+    let err = parse_error(
+        r#"<sequence values="BW" fill="B" width="5" height="5"><put x="0" y="0" value="R"/></sequence>"#,
+    );
+    assert!(matches!(err, ModelError::UnknownSymbol { symbol: 'R', .. }));
+
+    // placements only make sense before the first node runs
+    let err = parse_error(
+        r#"<sequence fill="B" width="5" height="5"><sequence><put x="0" y="0" value="W"/></sequence></sequence>"#,
+    );
+    assert!(matches!(err, ModelError::UnknownNode { tag, .. } if tag == "put"));
+}
+
+#[test]
+fn test_wrap_attribute() {
+    let wrap = |attributes: &str| {
+        let xml = format!(r#"<sequence fill="B" width="3" height="3" {attributes}/>"#);
+        parse_xml(&xml, Some(0)).unwrap().0.wrap
+    };
+
+    assert_eq!(wrap(""), Wrap::NONE);
+    assert_eq!(wrap(r#"periodic="False""#), Wrap::NONE);
+    assert_eq!(wrap(r#"periodic="True""#), Wrap::ALL);
+    let axes = |x, y, z| Wrap { x, y, z };
+    assert_eq!(wrap(r#"wrap="x""#), axes(true, false, false));
+    assert_eq!(wrap(r#"wrap="y""#), axes(false, true, false));
+    assert_eq!(wrap(r#"wrap="xy""#), axes(true, true, false));
+    assert_eq!(wrap(r#"wrap="zx""#), axes(true, false, true));
+    assert_eq!(wrap(r#"wrap="xyz""#), Wrap::ALL);
+
+    let err = parse_error(r#"<sequence fill="B" width="5" height="5" wrap="w"/>"#);
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"w\" for `wrap`: expected some of the axes x, y and z, e.g. xy"
+    );
+    let err = parse_error(r#"<sequence fill="B" width="5" height="5" wrap="xx"/>"#);
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"xx\" for `wrap`: axis x is repeated"
+    );
+
+    let err = parse_error(r#"<sequence fill="B" width="5" height="5" periodic="yes"/>"#);
+    assert!(
+        matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "periodic")
+    );
+
+    let err = parse_error(r#"<sequence fill="B" width="5" height="5" periodic="True" wrap="x"/>"#);
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "wrap"));
+}
+
+#[test]
+fn test_periodic_model_matches_across_edges() {
+    // the only `WB` pair of the grid is split between the last and the first column
+    let xml = r#"<sequence fill="B" width="4" height="1" periodic="True">
+  <put x="3" y="0" value="W"/>
+  <one in="WB" out="WR" symmetry="()"/>
+</sequence>"#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    assert!(mj.apply_sequence(&sequence, true));
+    assert_eq!(mj.grid_symbols().into_iter().collect::<String>(), "RBBW");
+}
+
+#[test]
+fn test_border_grows_walls_along_edges() {
+    let xml = r##"<sequence values="BW" fill="B" width="5" height="4" border="#">
+  <all in="#B" out="*W"/>
+</sequence>"##;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    assert!(mj.apply_sequence(&sequence, true));
+    assert_eq!(
+        mj.grid_symbols().into_iter().collect::<String>(),
+        "WWWWW\
+         WBBBW\
+         WBBBW\
+         WWWWW"
+    );
+
+    // a grid that wraps around has no border along that axis
+    let xml = r#"<sequence fill="B" width="5" height="4" wrap="x" border="_">
+  <all in="_B" out="*W"/>
+</sequence>"#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    assert!(mj.apply_sequence(&sequence, true));
+    assert_eq!(
+        mj.grid_symbols().into_iter().collect::<String>(),
+        "WWWWW\
+         BBBBB\
+         BBBBB\
+         WWWWW"
+    );
+}
+
+#[test]
+fn test_border_has_to_be_declared() {
+    // `#` is a symbol like any other without `border`
+    let xml = r##"<sequence fill="#" width="3" height="1">
+  <all in="#" out="W"/>
+</sequence>"##;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    assert!(mj.apply_sequence(&sequence, true));
+    assert_eq!(mj.grid_symbols(), vec!['W'; 3]);
+
+    let err = parse_error(
+        r##"<sequence values="BW" fill="B" width="5" height="4"><all in="#B" out="*W"/></sequence>"##,
+    );
+    assert_eq!(
+        err.to_string(),
+        "unknown symbol '#' in `in`, did you mean 'B'?"
+    );
+}
+
+#[test]
+fn test_invalid_border() {
+    let err = parse_error(r##"<sequence values="B#" fill="B" width="5" height="5" border="#"/>"##);
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "border"));
+
+    let err = parse_error(r#"<sequence fill="B" width="5" height="5" border="*"/>"#);
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "border"));
+
+    let err = parse_error(
+        r##"<sequence fill="B" width="5" height="5" border="#"><one in="#B" out="WW"/></sequence>"##,
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"WW\" for `out`: cells that are '#' in `in` must be '*' in `out`"
+    );
+
+    let err = parse_error(
+        r##"<sequence fill="B" width="5" height="5" border="#"><one in="B" out="#"/></sequence>"##,
+    );
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "out"));
+
+    let err = parse_error(
+        r##"<sequence fill="B" width="5" height="5" border="#"><union symbol="#" values="B"/></sequence>"##,
+    );
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "symbol"));
+}
+
+#[test]
+fn test_conflicts_attribute() {
+    let xml = r#"<sequence fill="B" width="3" height="3"><prl in="B" out="W" conflicts="skip"/></sequence>"#;
+    let (_, sequence) = parse_xml(xml, Some(0)).unwrap();
+    assert!(matches!(
+        &sequence.vec[0],
+        RuleOrSequence::Rule(Rule {
+            kind: RuleKind::Parallel(ConflictPolicy::Skip),
+            ..
+        })
+    ));
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3"><prl in="B" out="W" conflicts="first"/></sequence>"#,
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"first\" for `conflicts`: expected random or skip"
+    );
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3"><all in="B" out="W" conflicts="skip"/></sequence>"#,
+    );
+    assert!(
+        matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "conflicts")
+    );
+}
+
+#[test]
+fn test_3d_model_fills_every_layer() {
+    let xml = r#"<markov values="BW" fill="B" width="3" height="2" length="3" origin="True">
+  <one in="WB" out="WW"/>
+</markov>"#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+    assert_eq!((mj.width, mj.height, mj.length), (3, 2, 3));
+    assert_eq!(mj.grid_symbols().iter().filter(|&&c| c == 'W').count(), 1);
+
+    assert!(mj.apply_sequence(&sequence, true));
+    assert!(mj.grid_symbols().iter().all(|&c| c == 'W'));
+
+    let mut output = Vec::new();
+    mj.write_grid(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "WWW\nWWW\n\nWWW\nWWW\n\nWWW\nWWW\n"
+    );
+
+    // square symmetries keep to the layer of the origin
+    let xml = r#"<markov values="BW" fill="B" width="3" height="1" length="3" origin="True">
+  <one in="WB" out="WW" symmetry="(xy)"/>
+</markov>"#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    assert!(mj.apply_sequence(&sequence, true));
+    assert_eq!(
+        mj.grid_symbols().into_iter().collect::<String>(),
+        "BBBWWWBBB"
+    );
+}
+
+#[test]
+fn test_3d_patterns_with_layers() {
+    let xml = r#"<sequence values="BWR" fill="B" width="1" height="1" length="4">
+  <put x="0" y="0" z="3" value="W"/>
+  <one in="W B" out="W R" symmetry="()"/>
+</sequence>"#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    // the first layer written is the top one, so the cell below W turns red
+    assert!(mj.apply_sequence(&sequence, true));
+    assert_eq!(mj.grid_symbols().into_iter().collect::<String>(), "BBRW");
+
+    let xml = r#"<sequence values="BWR" fill="B" width="1" height="1" length="4" periodic="True">
+  <put x="0" y="0" z="3" value="W"/>
+  <one in="B W" out="R W" symmetry="()"/>
+</sequence>"#;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    // the pair is split between the top and the bottom layer
+    assert!(mj.apply_sequence(&sequence, true));
+    assert_eq!(mj.grid_symbols().into_iter().collect::<String>(), "RBBW");
+
+    let xml = r##"<sequence values="BW" fill="B" width="3" height="3" length="3" border="#">
+  <all in="#B" out="*W"/>
+</sequence>"##;
+    let (mut mj, sequence) = parse_xml(xml, Some(0)).unwrap();
+
+    // every cell but the centre one lies along the border
+    assert!(mj.apply_sequence(&sequence, true));
+    let symbols = mj.grid_symbols();
+    assert_eq!(symbols.iter().filter(|&&c| c == 'B').count(), 1);
+    assert_eq!(symbols[13], 'B');
+}
+
+#[test]
+fn test_invalid_3d_models() {
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3"><one in="B B" out="W W"/></sequence>"#,
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"B B\" for `in`: the pattern has 2 layers but the grid has a single one, set the root `length`"
+    );
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3" length="2"><one in="B B" out="W"/></sequence>"#,
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"W\" for `out`: output is 1x1 but input is 1x1x2"
+    );
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3" length="2"><put x="0" y="0" z="2" value="W"/></sequence>"#,
+    );
+    assert_eq!(
+        err.to_string(),
+        "invalid value \"2\" for `z`: outside the 3x3x2 grid"
+    );
+
+    let err = parse_error(
+        r#"<sequence fill="B" width="3" height="3" length="2"><put x="0" y="0" value="W"/></sequence>"#,
+    );
+    assert!(matches!(err, ModelError::MissingAttribute { attribute, .. } if attribute == "z"));
+
+    let err = parse_error(r#"<sequence fill="B" width="3" height="3" length="0"/>"#);
+    assert!(matches!(err, ModelError::InvalidAttribute { attribute, .. } if attribute == "length"));
+}